@@ -0,0 +1,8 @@
+/// Environment variable holding the passphrase of an encrypted database
+pub const DB_KEY_ENV: &str = "PC_MONITOR_DB_KEY";
+
+/// Environment variable overriding the path of the key file
+pub const DB_KEY_FILE_ENV: &str = "PC_MONITOR_DB_KEY_FILE";
+
+/// File of the config dir holding the passphrase, when the environment has none
+pub const DB_KEY_FILE_NAME: &str = "db.key";
@@ -0,0 +1,26 @@
+pub const CONFIG_NAME: &str = "config.json";
+
+/// Hour of the day at which a new day starts in the statistics
+pub const DEFAULT_DAY_START_HOUR: u32 = 0;
+
+/// Days of raw app usage logs kept, older ones are deleted once aggregated
+pub const DEFAULT_LOG_RETENTION_DAYS: u64 = 90;
+
+/// Daily backups of the database kept, older ones are deleted
+pub const DEFAULT_BACKUP_COUNT: usize = 7;
+
+/// Name of the app's folder in the platform config and data dirs, same as the
+/// identifier in `tauri.conf.json`
+pub const APP_IDENTIFIER: &str = "com.yzh.pc-monitor";
+
+/// Environment variable overriding the data dir
+pub const DATA_DIR_ENV: &str = "PC_MONITOR_DATA_DIR";
+
+/// Folder of the data dir where log files are written
+pub const LOG_DIR_NAME: &str = "logs";
+
+/// Seconds without input before the user is considered idle
+pub const DEFAULT_IDLE_THRESHOLD_SECS: u64 = 300;
+
+/// Seconds between two samples of the foreground window, 0 disables polling
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 10;
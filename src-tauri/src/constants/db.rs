@@ -1,8 +1,40 @@
 pub const DB_NAME: &str = "pc_monitor.db";
+
+/// How long a connection waits for a lock held by another one
+pub const DB_BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// Idle reader connections kept open for the commands
+pub const DB_READER_POOL_SIZE: usize = 4;
+
+/// The most operations the writer commits in one transaction
+pub const WRITER_BATCH_SIZE: usize = 256;
+
+/// How long the writer buffers operations before committing them
+pub const WRITER_FLUSH_INTERVAL_MS: u64 = 1000;
+
+/// Folder of the data dir where backups are written
+pub const BACKUP_DIR_NAME: &str = "backups";
+
+/// Backup file names are the prefix followed by the UTC time and `.db`
+pub const BACKUP_PREFIX: &str = "pc_monitor_";
+
+/// How often the database is backed up
+pub const BACKUP_INTERVAL_SECS: u64 = 24 * 3600;
+
+/// The largest UTC offset in use, bounds the epoch range of a local day in queries
+pub const MAX_UTC_OFFSET_MS: i64 = 14 * 3600 * 1000;
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct TABLE;
 
 impl TABLE {
     pub const APP_USAGE_LOGS: &str = "app_usage_logs";
     pub const DAILY_APP_USAGE: &str = "daily_app_usage";
     pub const DAILY_USAGE_STATS: &str = "daily_usage_stats";
+    pub const APP_SESSIONS: &str = "app_sessions";
+    pub const FINALIZED_DAYS: &str = "finalized_days";
+    pub const HOURLY_APP_USAGE: &str = "hourly_app_usage";
+    pub const DAILY_FOCUS_STATS: &str = "daily_focus_stats";
+    pub const CATEGORIES: &str = "categories";
+    pub const APP_CATEGORIES: &str = "app_categories";
 }
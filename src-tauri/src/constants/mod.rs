@@ -1,3 +1,8 @@
+#[cfg(feature = "encryption")]
+pub mod cipher;
+pub mod config;
 pub mod db;
 pub mod report;
+pub mod stats;
+pub mod tracker;
 pub mod window;
@@ -0,0 +1,8 @@
+/// Focus sessions shorter than this count as fragmented
+pub const FOCUS_SHORT_SESSION_SECS: u64 = 60;
+
+/// Timeline segments shorter than this, once merged, are dropped
+pub const TIMELINE_MIN_SEGMENT_SECS: i64 = 60;
+
+/// Segments of the same app this close are merged into one on the timeline
+pub const TIMELINE_MERGE_GAP_SECS: i64 = 60;
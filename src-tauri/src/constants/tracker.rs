@@ -0,0 +1,5 @@
+/// Seconds between two heartbeats written by the tracker
+pub const HEARTBEAT_INTERVAL_SECS: i64 = 60;
+
+/// A gap between logs longer than this means the PC was suspended or off
+pub const SUSPEND_GAP_SECS: i64 = 3 * HEARTBEAT_INTERVAL_SECS;
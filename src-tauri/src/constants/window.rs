@@ -5,6 +5,8 @@ pub struct WindowEvent;
 impl WindowEvent {
     pub const EXITED: &str = "Application Exited";
     pub const LOCKED: &str = "LockApp.exe";
+    pub const IDLE: &str = "User Idle";
+    pub const HEARTBEAT: &str = "Heartbeat";
 }
 
 pub const W_IGNORE_APP_LIST: &[&str] = &[
@@ -22,4 +24,5 @@ pub const R_IGNORE_APP_LIST: &[&str] = &[
     "TaskManager",
     WindowEvent::LOCKED,
     WindowEvent::EXITED,
+    WindowEvent::IDLE,
 ];
@@ -0,0 +1,302 @@
+use crate::core::task::{RT, register_event_listener};
+use crate::logging;
+use crate::utils::config::ConfigManager;
+use crate::utils::logging::Type;
+use crate::utils::window::{ForegroundWindow, current_window};
+use chrono::{DateTime, Utc};
+use rdev::{Event, EventType};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// An event reported by an [`ActivitySource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActivityEvent {
+    /// The app and window title in the foreground at `time`
+    Focus {
+        time: DateTime<Utc>,
+        app_name: String,
+        title: String,
+        /// The name of the source which observed the window
+        source: &'static str,
+    },
+    /// Keyboard or mouse input at `time`
+    Input { time: DateTime<Utc> },
+}
+
+impl ActivityEvent {
+    pub fn focus(window: ForegroundWindow, source: &'static str) -> Self {
+        ActivityEvent::Focus {
+            time: Utc::now(),
+            app_name: window.app_name,
+            title: window.title,
+            source,
+        }
+    }
+
+    pub fn input() -> Self {
+        ActivityEvent::Input { time: Utc::now() }
+    }
+}
+
+/// A producer of focus-change events.
+///
+/// Sources only report what they observe; deduplication and persistence are
+/// left to the [`Tracker`](crate::core::tracker::Tracker) on the receiving end.
+pub trait ActivitySource: Send {
+    /// The name of the source, used in logs and recorded with sessions.
+    fn name(&self) -> &'static str;
+
+    /// Start sending events into `sender`.
+    fn start(&mut self, sender: Sender<ActivityEvent>) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Stop sending events.
+    fn stop(&mut self);
+}
+
+/// Get the activity source for the current platform.
+pub fn default_source() -> Box<dyn ActivitySource> {
+    #[cfg(target_os = "linux")]
+    if let Some(compositor) = crate::utils::wayland::Compositor::detect() {
+        return Box::new(WaylandSource::new(compositor));
+    }
+    Box::new(InputHookSource::new())
+}
+
+/// Reports input, and looks up the foreground window whenever a mouse button or key
+/// is released.
+pub struct InputHookSource {
+    running: Arc<AtomicBool>,
+    /// Whether to look up the foreground window, off when another source reports focus
+    report_focus: bool,
+}
+
+impl InputHookSource {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            report_focus: true,
+        }
+    }
+
+    /// Only report input, for sources which can't see it themselves
+    pub fn input_only() -> Self {
+        Self {
+            report_focus: false,
+            ..Self::new()
+        }
+    }
+}
+
+impl ActivitySource for InputHookSource {
+    fn name(&self) -> &'static str {
+        "input_hook"
+    }
+
+    fn start(&mut self, sender: Sender<ActivityEvent>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("Input hook source already started".into());
+        }
+        let running = self.running.clone();
+        let report_focus = self.report_focus;
+        let source = self.name();
+        thread::spawn(move || {
+            // Mouse moves arrive in bursts, one input event per second is plenty
+            let mut last_input: Option<Instant> = None;
+            let result = register_event_listener("move_click", move |evt: Event| {
+                if !running.load(Ordering::SeqCst) {
+                    return;
+                }
+                if last_input.is_none_or(|t| t.elapsed() >= Duration::from_secs(1)) {
+                    last_input = Some(Instant::now());
+                    let _ = sender.send(ActivityEvent::input());
+                }
+                match evt.event_type {
+                    EventType::ButtonRelease(_) | EventType::KeyRelease(_) if report_focus => {
+                        let _ = sender.send(ActivityEvent::focus(current_window(), source));
+                    }
+                    _ => {}
+                }
+            });
+            if let Err(e) = result {
+                logging!(
+                    warn,
+                    Type::Window,
+                    true,
+                    "Failed to listen for input, idle time will be counted as usage: {:?}",
+                    e
+                );
+            }
+        });
+        Ok(())
+    }
+
+    /// The input hook can't be removed, so this only mutes the callback.
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Receives focus events from the sway or Hyprland IPC socket.
+///
+/// The socket doesn't report input, so an input-only [`InputHookSource`] runs
+/// alongside for idle detection.
+#[cfg(target_os = "linux")]
+pub struct WaylandSource {
+    compositor: crate::utils::wayland::Compositor,
+    input: InputHookSource,
+    running: Arc<AtomicBool>,
+}
+
+#[cfg(target_os = "linux")]
+impl WaylandSource {
+    pub fn new(compositor: crate::utils::wayland::Compositor) -> Self {
+        Self {
+            compositor,
+            input: InputHookSource::input_only(),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ActivitySource for WaylandSource {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn start(&mut self, sender: Sender<ActivityEvent>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("Wayland source already started".into());
+        }
+        let source = self.name();
+        if let Some(window) = self.compositor.focused()? {
+            sender.send(ActivityEvent::focus(window.foreground(), source))?;
+        }
+        if let Err(e) = self.input.start(sender.clone()) {
+            logging!(
+                warn,
+                Type::Window,
+                true,
+                "Failed to start input hook, idle time will be counted as usage: {}",
+                e
+            );
+        }
+        let compositor = self.compositor.clone();
+        let running = self.running.clone();
+        thread::spawn(move || {
+            let focus = crate::utils::wayland::WaylandFocus::global();
+            while running.load(Ordering::SeqCst) {
+                let result = compositor.subscribe(|window| {
+                    if running.load(Ordering::SeqCst) {
+                        let window = window.foreground();
+                        focus.set_focused(Some(window.clone()));
+                        let _ = sender.send(ActivityEvent::focus(window, source));
+                    }
+                });
+                // `current_window` queries the compositor until the subscription is back
+                focus.set_focused(None);
+                logging!(
+                    warn,
+                    Type::Window,
+                    false,
+                    "Compositor focus subscription ended: {:?}",
+                    result
+                );
+                thread::sleep(Duration::from_secs(5));
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.input.stop();
+    }
+}
+
+/// Samples the foreground window on the `RT` runtime every `poll_interval_secs`.
+///
+/// Catches windows which gain focus by themselves, e.g. notification clicks or
+/// auto-raise. Samples of an unchanged window are dropped by the tracker.
+pub struct PollingSource {
+    running: Arc<AtomicBool>,
+}
+
+impl PollingSource {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl ActivitySource for PollingSource {
+    fn name(&self) -> &'static str {
+        "polling"
+    }
+
+    fn start(&mut self, sender: Sender<ActivityEvent>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("Polling source already started".into());
+        }
+        let running = self.running.clone();
+        let source = self.name();
+        RT.spawn(async move {
+            while running.load(Ordering::SeqCst) {
+                // Read on every round, so a new frequency applies without a restart
+                let secs = ConfigManager::global().get().poll_interval_secs;
+                if secs == 0 {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+                let Ok(window) = tokio::task::spawn_blocking(current_window).await else {
+                    continue;
+                };
+                if sender.send(ActivityEvent::focus(window, source)).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Replays a scripted list of events, for driving the pipeline in tests.
+#[cfg(test)]
+pub struct MockSource {
+    events: Vec<ActivityEvent>,
+}
+
+#[cfg(test)]
+impl MockSource {
+    pub fn new(events: Vec<ActivityEvent>) -> Self {
+        Self { events }
+    }
+}
+
+#[cfg(test)]
+impl ActivitySource for MockSource {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    /// All events are sent before returning, so the order is deterministic.
+    fn start(&mut self, sender: Sender<ActivityEvent>) -> Result<(), Box<dyn std::error::Error>> {
+        for event in self.events.drain(..) {
+            sender.send(event)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.events.clear();
+    }
+}
@@ -0,0 +1,266 @@
+use crate::utils::config::ConfigManager;
+use crate::utils::db::DbManager;
+use crate::utils::repo::{self, Category};
+use crate::utils::time::local_date;
+use crate::utils::writer::{DbWriter, WriteOp, WriteSink};
+use chrono::{Days, NaiveDate};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// The usage of a day, in seconds, by category
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CategoryUsage {
+    pub categories: HashMap<String, u64>,
+    /// The apps in no category
+    pub uncategorized: u64,
+}
+
+/// Trim `name`, rejecting it if it's empty or another category has it already
+fn check_name(
+    conn: &Connection,
+    name: &str,
+    id: Option<i64>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Category name is empty".into());
+    }
+    if repo::category_by_name(conn, name)?.is_some_and(|category| Some(category.id) != id) {
+        return Err(format!("Category {} already exists", name).into());
+    }
+    Ok(name.to_string())
+}
+
+fn check_exists(conn: &Connection, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+    match repo::category_by_id(conn, id)? {
+        Some(_) => Ok(()),
+        None => Err(format!("No category {}", id).into()),
+    }
+}
+
+pub fn create(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    name: &str,
+) -> Result<Category, Box<dyn std::error::Error>> {
+    let name = check_name(conn, name, None)?;
+    sink.write(WriteOp::InsertCategory { name: name.clone() })?;
+    sink.flush()?;
+    Ok(repo::category_by_name(conn, &name)?.ok_or("Failed to create category")?)
+}
+
+pub fn rename(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    id: i64,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_exists(conn, id)?;
+    let name = check_name(conn, name, Some(id))?;
+    sink.write(WriteOp::RenameCategory { id, name })?;
+    sink.flush()
+}
+
+pub fn delete(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    id: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_exists(conn, id)?;
+    sink.write(WriteOp::DeleteCategory { id })?;
+    sink.flush()
+}
+
+pub fn assign(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    app_name: &str,
+    category_id: Option<i64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(id) = category_id {
+        check_exists(conn, id)?;
+    }
+    sink.write(WriteOp::SetAppCategory {
+        app_name: app_name.to_string(),
+        category_id,
+    })?;
+    sink.flush()
+}
+
+/// Get the usage of each category from `start_date` to `end_date`, both included, by
+/// date
+fn get_category_usage_duration(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<HashMap<String, CategoryUsage>, rusqlite::Error> {
+    let mut result: HashMap<String, CategoryUsage> = HashMap::new();
+    for (date, category, total_usage) in repo::category_usage_between(conn, start_date, end_date)? {
+        let usage = result.entry(date.to_string()).or_default();
+        match category {
+            Some(name) => {
+                usage.categories.insert(name, total_usage);
+            }
+            None => usage.uncategorized = total_usage,
+        }
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn get_categories() -> Result<Vec<Category>, String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    repo::categories(&conn).map_err(|e| format!("Error occured: {}", e))
+}
+
+#[tauri::command]
+pub fn create_category(name: &str) -> Result<Category, String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    create(&conn, DbWriter::global(), name).map_err(|e| format!("Error occured: {}", e))
+}
+
+#[tauri::command]
+pub fn rename_category(id: i64, name: &str) -> Result<(), String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    rename(&conn, DbWriter::global(), id, name).map_err(|e| format!("Error occured: {}", e))
+}
+
+/// Delete a category, its apps become uncategorized
+#[tauri::command]
+pub fn delete_category(id: i64) -> Result<(), String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    delete(&conn, DbWriter::global(), id).map_err(|e| format!("Error occured: {}", e))
+}
+
+/// Get the category id of each categorized app, by app name
+#[tauri::command]
+pub fn get_app_categories() -> Result<HashMap<String, i64>, String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    match repo::app_categories(&conn) {
+        Ok(apps) => Ok(apps.into_iter().collect()),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
+/// Put `app_name` in the category `category_id`, or in none if it's not provided
+#[tauri::command]
+pub fn set_app_category(app_name: &str, category_id: Option<i64>) -> Result<(), String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    assign(&conn, DbWriter::global(), app_name, category_id)
+        .map_err(|e| format!("Error occured: {}", e))
+}
+
+#[tauri::command]
+pub fn get_category_usage_duration_last_n_days(
+    n: u64,
+) -> Result<HashMap<String, CategoryUsage>, String> {
+    let now_date = local_date(ConfigManager::global().get().day_start_hour);
+    let start_date = now_date - Days::new(n);
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    get_category_usage_duration(&conn, start_date, now_date)
+        .map_err(|e| format!("Error occured: {}", e))
+}
+
+#[tauri::command]
+pub fn get_category_usage_duration_range(
+    start_date: &str,
+    end_date: &str,
+) -> Result<HashMap<String, CategoryUsage>, String> {
+    let start_date =
+        NaiveDate::parse_from_str(start_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let end_date = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    get_category_usage_duration(&conn, start_date, end_date)
+        .map_err(|e| format!("Error occured: {}", e))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::utils::repo::DailyAppUsage;
+    use crate::utils::test::memory_db;
+
+    #[test]
+    pub fn test_categories() {
+        let conn = memory_db();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 9, day).unwrap();
+        for (day, app_name, total_usage) in [
+            (1, "Visual Studio Code", 3600),
+            (1, "Terminal", 600),
+            (1, "Slack", 300),
+            (1, "Steam", 100),
+            (2, "Slack", 200),
+        ] {
+            let usage = DailyAppUsage {
+                date: date(day),
+                app_name: app_name.to_string(),
+                total_usage,
+            };
+            repo::upsert_daily_app_usage(&conn, &usage).unwrap();
+        }
+
+        let development = create(&conn, &conn, " Development ").unwrap();
+        assert_eq!(development.name, "Development");
+        let chat = create(&conn, &conn, "Chat").unwrap();
+        assert!(create(&conn, &conn, "Development").is_err());
+        assert!(create(&conn, &conn, "  ").is_err());
+        rename(&conn, &conn, chat.id, "Communication").unwrap();
+        assert!(rename(&conn, &conn, chat.id, "Development").is_err());
+        assert!(rename(&conn, &conn, 42, "Games").is_err());
+
+        assign(&conn, &conn, "Visual Studio Code", Some(development.id)).unwrap();
+        assign(&conn, &conn, "Terminal", Some(development.id)).unwrap();
+        assign(&conn, &conn, "Slack", Some(chat.id)).unwrap();
+        assert!(assign(&conn, &conn, "Steam", Some(42)).is_err());
+
+        let usage = get_category_usage_duration(&conn, date(1), date(2)).unwrap();
+        assert_eq!(
+            usage["2025-09-01"],
+            CategoryUsage {
+                categories: HashMap::from([
+                    ("Development".to_string(), 4200),
+                    ("Communication".to_string(), 300),
+                ]),
+                uncategorized: 100,
+            }
+        );
+        assert_eq!(usage["2025-09-02"].categories["Communication"], 200);
+
+        // Its apps become uncategorized
+        delete(&conn, &conn, development.id).unwrap();
+        assign(&conn, &conn, "Slack", None).unwrap();
+        assert_eq!(
+            repo::categories(&conn).unwrap(),
+            vec![Category {
+                id: chat.id,
+                name: "Communication".to_string(),
+            }]
+        );
+        assert!(repo::app_categories(&conn).unwrap().is_empty());
+        let usage = get_category_usage_duration(&conn, date(1), date(1)).unwrap();
+        assert_eq!(
+            usage["2025-09-01"],
+            CategoryUsage {
+                categories: HashMap::new(),
+                uncategorized: 4600,
+            }
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use crate::utils::config::ConfigManager;
+use crate::utils::db::DbManager;
+use crate::utils::repo;
+use crate::utils::time::local_date;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// A calendar period, weeks are ISO ones from Monday
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    /// Get the first and last day of the period { date } is in
+    pub fn bounds(self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let start = match self {
+            Period::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            Period::Month => date.with_day(1).unwrap(),
+            Period::Year => date.with_ordinal(1).unwrap(),
+        };
+        let next = match self {
+            Period::Week => start + Days::new(7),
+            Period::Month => start + Months::new(1),
+            Period::Year => start + Months::new(12),
+        };
+        (start, next - Days::new(1))
+    }
+}
+
+/// The usage over a period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeriodSummary {
+    /// `%Y-%m-%d`
+    pub start_date: String,
+    pub end_date: String,
+    /// Seconds
+    pub total_usage: u64,
+    /// Apps used at least once
+    pub active_apps: u32,
+    /// Seconds per day, over the days up to today only
+    pub daily_average: u64,
+}
+
+/// A period against the one before it, the changes are `current - previous`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeriodComparison {
+    pub current: PeriodSummary,
+    pub previous: PeriodSummary,
+    pub total_usage_change: i64,
+    pub active_apps_change: i64,
+    pub daily_average_change: i64,
+}
+
+fn summarize(
+    conn: &Connection,
+    (start_date, end_date): (NaiveDate, NaiveDate),
+    today: NaiveDate,
+) -> Result<PeriodSummary, rusqlite::Error> {
+    let (total_usage, active_apps) = repo::usage_summary(conn, start_date, end_date)?;
+    // A period which isn't over yet is averaged over its days so far
+    let days = (end_date.min(today) - start_date).num_days() + 1;
+    let daily_average = if days > 0 {
+        total_usage / days as u64
+    } else {
+        0
+    };
+    Ok(PeriodSummary {
+        start_date: start_date.to_string(),
+        end_date: end_date.to_string(),
+        total_usage,
+        active_apps,
+        daily_average,
+    })
+}
+
+/// Compare the { period } { anchor_date } is in with the one before it.
+pub fn compare(
+    conn: &Connection,
+    period: Period,
+    anchor_date: NaiveDate,
+    today: NaiveDate,
+) -> Result<PeriodComparison, rusqlite::Error> {
+    let bounds = period.bounds(anchor_date);
+    let current = summarize(conn, bounds, today)?;
+    let previous = summarize(conn, period.bounds(bounds.0 - Days::new(1)), today)?;
+    Ok(PeriodComparison {
+        total_usage_change: current.total_usage as i64 - previous.total_usage as i64,
+        active_apps_change: current.active_apps as i64 - previous.active_apps as i64,
+        daily_average_change: current.daily_average as i64 - previous.daily_average as i64,
+        current,
+        previous,
+    })
+}
+
+/// Compare the week, month or year `anchor_date` is in with the one before it.
+#[tauri::command]
+pub fn compare_periods(period: Period, anchor_date: &str) -> Result<PeriodComparison, String> {
+    let anchor_date =
+        NaiveDate::parse_from_str(anchor_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let today = local_date(ConfigManager::global().get().day_start_hour);
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    match compare(&conn, period, anchor_date, today) {
+        Ok(comparison) => Ok(comparison),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::utils::repo::DailyAppUsage;
+    use crate::utils::test::memory_db;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    pub fn test_period_bounds() {
+        // A Wednesday, ISO week 1 of 2025 starts in 2024
+        let anchor = date(2025, 1, 1);
+        assert_eq!(
+            Period::Week.bounds(anchor),
+            (date(2024, 12, 30), date(2025, 1, 5))
+        );
+        assert_eq!(
+            Period::Week.bounds(date(2025, 1, 5)),
+            (date(2024, 12, 30), date(2025, 1, 5))
+        );
+        assert_eq!(
+            Period::Month.bounds(date(2024, 2, 10)),
+            (date(2024, 2, 1), date(2024, 2, 29))
+        );
+        assert_eq!(
+            Period::Month.bounds(date(2024, 12, 31)),
+            (date(2024, 12, 1), date(2024, 12, 31))
+        );
+        assert_eq!(
+            Period::Year.bounds(anchor),
+            (date(2025, 1, 1), date(2025, 12, 31))
+        );
+    }
+
+    #[test]
+    pub fn test_compare() {
+        let conn = memory_db();
+        let usage = |date, app_name: &str, total_usage| {
+            let usage = DailyAppUsage {
+                date,
+                app_name: app_name.to_string(),
+                total_usage,
+            };
+            repo::upsert_daily_app_usage(&conn, &usage).unwrap();
+        };
+        // February, 29 days
+        usage(date(2024, 2, 1), "Firefox", 2900);
+        usage(date(2024, 2, 29), "Terminal", 2900);
+        // March up to the 10th
+        usage(date(2024, 3, 1), "Firefox", 3000);
+        usage(date(2024, 3, 10), "Firefox", 1000);
+
+        let comparison =
+            compare(&conn, Period::Month, date(2024, 3, 5), date(2024, 3, 10)).unwrap();
+        assert_eq!(
+            comparison.current,
+            PeriodSummary {
+                start_date: "2024-03-01".into(),
+                end_date: "2024-03-31".into(),
+                total_usage: 4000,
+                active_apps: 1,
+                daily_average: 400,
+            }
+        );
+        assert_eq!(comparison.previous.start_date, "2024-02-01");
+        assert_eq!(comparison.previous.daily_average, 200);
+        assert_eq!(comparison.total_usage_change, -1800);
+        assert_eq!(comparison.active_apps_change, -1);
+        assert_eq!(comparison.daily_average_change, 200);
+
+        // Nothing to average yet
+        let comparison = compare(&conn, Period::Year, date(2025, 6, 1), date(2024, 3, 10)).unwrap();
+        assert_eq!(comparison.current.daily_average, 0);
+        assert_eq!(comparison.previous.total_usage, 9800);
+        assert_eq!(comparison.previous.daily_average, 9800 / 70);
+    }
+}
@@ -1,3 +1,8 @@
+pub mod activity;
+pub mod category;
+pub mod compare;
 pub mod report;
+pub mod retention;
 pub mod stats;
 pub mod task;
+pub mod tracker;
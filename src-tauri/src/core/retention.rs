@@ -0,0 +1,185 @@
+use crate::logging;
+use crate::utils::logging::Type;
+use crate::utils::repo;
+use crate::utils::writer::{WriteOp, WriteSink};
+use chrono::{Days, NaiveDate};
+use rusqlite::Connection;
+
+/// What a compaction removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Days whose logs were deleted
+    pub days: usize,
+    /// Days past retention whose logs were kept, because their aggregates aren't
+    /// finalized or are missing
+    pub skipped_days: usize,
+    pub deleted_logs: i64,
+    pub reclaimed_bytes: i64,
+}
+
+/// Delete the app usage logs of the days more than `retention_days` before { today },
+/// then vacuum the database. A day's logs are only deleted once its aggregates are
+/// finalized and written, the others wait for a later compaction.
+///
+/// A `retention_days` of 0 keeps all logs.
+pub fn compact_logs(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    today: NaiveDate,
+    retention_days: u64,
+    day_start_hour: u32,
+) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+    let mut report = CompactionReport::default();
+    if retention_days == 0 {
+        return Ok(report);
+    }
+    let first_kept_day = today - Days::new(retention_days);
+    let logs_before = repo::count_logs(conn)?;
+    let size_before = repo::database_size(conn)?;
+
+    for date in repo::log_days_before(conn, first_kept_day, day_start_hour)? {
+        if !repo::is_day_finalized(conn, date)? || !repo::has_aggregates(conn, date)? {
+            logging!(
+                warn,
+                Type::Database,
+                false,
+                "Keeping the logs of {}, its aggregates aren't finalized or are missing",
+                date
+            );
+            report.skipped_days += 1;
+            continue;
+        }
+        sink.write(WriteOp::DeleteLogsDuring {
+            date,
+            day_start_hour,
+        })?;
+        report.days += 1;
+    }
+    if report.days > 0 {
+        sink.write(WriteOp::Vacuum)?;
+    }
+    sink.flush()?;
+
+    report.deleted_logs = logs_before - repo::count_logs(conn)?;
+    report.reclaimed_bytes = size_before - repo::database_size(conn)?;
+    logging!(
+        info,
+        Type::Database,
+        false,
+        "Compacted logs before {}: {} days, {} logs deleted, {} bytes reclaimed, {} days kept until finalized",
+        first_kept_day,
+        report.days,
+        report.deleted_logs,
+        report.reclaimed_bytes,
+        report.skipped_days
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::constants::window::WindowEvent;
+    use crate::core::stats::finalize_past_days;
+    use crate::utils::repo::{LogRow, SessionRow};
+    use crate::utils::test::memory_db;
+    use chrono::DateTime;
+
+    /// Log a window at `time`, with its session of 10 seconds
+    fn log(conn: &Connection, time: &str, app_name: &str) {
+        let time_ms = DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .timestamp_millis();
+        let log = LogRow {
+            time_ms,
+            utc_offset: 8 * 3600,
+            app_name: app_name.to_string(),
+            title: "x".repeat(1000),
+        };
+        repo::insert_log(conn, &log).unwrap();
+        let session = SessionRow {
+            id: 0,
+            start_ms: time_ms,
+            end_ms: time_ms + 10 * 1000,
+            utc_offset: log.utc_offset,
+            app_name: log.app_name,
+            title: String::new(),
+            source: "mock".to_string(),
+        };
+        repo::insert_session(conn, &session).unwrap();
+    }
+
+    #[test]
+    pub fn test_compact_logs() {
+        let conn = memory_db();
+        // Sep 1 to 4 in UTC+8, each day 100 logs of 10 seconds from 09:00
+        for day in 1..=4 {
+            for n in 0..100 {
+                let time = format!("2025-09-0{}T01:{:02}:{:02}Z", day, n / 6, n % 6 * 10);
+                log(
+                    &conn,
+                    &time,
+                    if n % 2 == 0 { "Firefox" } else { "Terminal" },
+                );
+            }
+        }
+        finalize_past_days(&conn, &conn, 0).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 9, day).unwrap();
+        let totals = repo::daily_totals(&conn, date(1), date(4)).unwrap();
+
+        // Sep 2 lost its finalization, its logs wait for the next compaction
+        conn.execute("DELETE FROM finalized_days WHERE date = '2025-09-02'", [])
+            .unwrap();
+        // Sep 3 is finalized, but the write of its app usage failed
+        conn.execute("DELETE FROM daily_app_usage WHERE date = '2025-09-03'", [])
+            .unwrap();
+        let report = compact_logs(&conn, &conn, date(5), 1, 0).unwrap();
+        assert_eq!(report.days, 1);
+        assert_eq!(report.skipped_days, 2);
+        assert_eq!(report.deleted_logs, 100);
+        assert!(report.reclaimed_bytes > 0);
+        assert_eq!(repo::count_logs(&conn).unwrap(), 300);
+        assert_eq!(
+            repo::log_days_before(&conn, date(5), 0).unwrap(),
+            vec![date(2), date(3), date(4)]
+        );
+
+        // The aggregates don't depend on the logs
+        assert_eq!(repo::daily_totals(&conn, date(1), date(4)).unwrap(), totals);
+
+        let report = compact_logs(&conn, &conn, date(5), 0, 0).unwrap();
+        assert_eq!(report, CompactionReport::default());
+    }
+
+    #[test]
+    pub fn test_compact_logs_of_day_without_sessions() {
+        let conn = memory_db();
+        log(&conn, "2025-09-01T01:00:00Z", "Firefox");
+        // Only heartbeats on Sep 2 in UTC+8, e.g. idle all day
+        for minute in 0..10 {
+            let time = format!("2025-09-02T01:{:02}:00Z", minute);
+            let heartbeat = LogRow {
+                time_ms: DateTime::parse_from_rfc3339(&time)
+                    .unwrap()
+                    .timestamp_millis(),
+                utc_offset: 8 * 3600,
+                app_name: WindowEvent::HEARTBEAT.to_string(),
+                title: String::new(),
+            };
+            repo::insert_log(&conn, &heartbeat).unwrap();
+        }
+        log(&conn, "2025-09-03T01:00:00Z", "Firefox");
+        finalize_past_days(&conn, &conn, 0).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 9, day).unwrap();
+        assert!(repo::is_day_finalized(&conn, date(2)).unwrap());
+        assert!(repo::has_aggregates(&conn, date(2)).unwrap());
+
+        let report = compact_logs(&conn, &conn, date(4), 1, 0).unwrap();
+        assert_eq!(report.days, 2);
+        assert_eq!(report.skipped_days, 0);
+        assert_eq!(
+            repo::log_days_before(&conn, date(4), 0).unwrap(),
+            vec![date(3)]
+        );
+    }
+}
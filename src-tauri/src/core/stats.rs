@@ -1,128 +1,441 @@
+use crate::constants::stats::{
+    FOCUS_SHORT_SESSION_SECS, TIMELINE_MERGE_GAP_SECS, TIMELINE_MIN_SEGMENT_SECS,
+};
+use crate::constants::tracker::SUSPEND_GAP_SECS;
 use crate::constants::window::R_IGNORE_APP_LIST;
 use crate::logging;
+use crate::utils::config::ConfigManager;
+use crate::utils::db::DbManager;
 use crate::utils::logging::Type;
+use crate::utils::repo::{
+    self, AppRank, DailyAppUsage, DailyUsageStats, FocusStats, HourlyAppUsage,
+};
 use crate::utils::test::jsonify;
-use crate::{constants::db::TABLE, utils::db::DbManager};
-use chrono::{Days, Local, NaiveDate, TimeZone, Utc};
-use rusqlite::{Connection, params};
+use crate::utils::time::{local_date, wall_clock_date, wall_clock_day_bounds};
+use crate::utils::writer::{DbWriter, WriteOp, WriteSink};
+use chrono::{Datelike, Days, NaiveDate, Utc};
+use rusqlite::Connection;
+use serde::Serialize;
 use std::collections::HashMap;
 
-/// Update the daily usage statistics for a specific application (data derived from app usage logs).
-pub fn update_daily_app_usage(conn: &Connection) -> Result<(), rusqlite::Error> {
+/// Update the daily usage statistics for a specific application (data derived from app sessions).
+///
+/// The writes are committed on return.
+pub fn update_daily_app_usage(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    day_start_hour: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Update usage duration for each app
-    let local_today = get_local_date();
-    let usage_map = collect_app_usage_duration(&conn, local_today)?;
-    for (key, val) in &usage_map {
-        let sql = format!(
-            "INSERT INTO {} (date, app_name, total_usage) VALUES (?, ?, ?) ON CONFLICT(date, app_name) DO UPDATE SET total_usage = ?",
-            TABLE::DAILY_APP_USAGE
-        );
-        let mut stmt = conn.prepare(&sql)?;
-        stmt.execute(params![local_today.to_string(), key, val, val])?;
+    let local_today = local_date(day_start_hour);
+    let usage_map = collect_app_usage_duration(conn, local_today, day_start_hour)?;
+    for (key, val) in usage_map {
+        let usage = DailyAppUsage {
+            date: local_today,
+            app_name: key,
+            total_usage: val.max(0) as u64,
+        };
+        sink.write(WriteOp::UpsertDailyAppUsage(usage))?;
     }
-    Ok(())
+    sink.write(WriteOp::ReplaceHourlyAppUsage {
+        date: local_today,
+        usage: collect_hourly_app_usage(conn, local_today, day_start_hour)?,
+    })?;
+    sink.write(WriteOp::UpsertDailyFocusStats {
+        date: local_today,
+        stats: collect_focus_stats(conn, local_today, day_start_hour)?,
+    })?;
+    sink.flush()
 }
 
 /// Update the daily usage statistics for all applications (data derived from daily app usage stats).
-pub fn update_daily_usage_stats(conn: &Connection) -> Result<(), rusqlite::Error> {
-    let today = get_local_date().to_string();
+pub fn update_daily_usage_stats(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    day_start_hour: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let today = local_date(day_start_hour);
 
     // Update usage duration for today
-    let duration = get_daily_usage_duration(&conn)?;
-    let sql = format!(
-        "INSERT INTO {} (date, total_usage) VALUES (?, ?) ON CONFLICT(date) DO UPDATE SET total_usage = ?",
-        TABLE::DAILY_USAGE_STATS
-    );
-    let mut stmt = conn.prepare(&sql)?;
-    stmt.execute(params![today, duration, duration])?;
-    Ok(())
+    let stats = DailyUsageStats {
+        date: today,
+        total_usage: repo::daily_app_usage_total(conn, today)?,
+    };
+    sink.write(WriteOp::UpsertDailyUsageStats(stats))?;
+    sink.flush()
 }
 
-/// Get all app usage durations that occurred today
-fn collect_app_usage_duration(
+/// Recompute the aggregates of { local_date } from the app sessions, replacing what is
+/// stored.
+fn recompute_day(
     conn: &Connection,
+    sink: &dyn WriteSink,
     local_date: NaiveDate,
-) -> Result<HashMap<String, i64>, rusqlite::Error> {
-    let (start_of_day, end_of_day) = get_local_day_start_end_in_utc(local_date);
+    day_start_hour: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let usage: Vec<DailyAppUsage> = collect_app_usage_duration(conn, local_date, day_start_hour)?
+        .into_iter()
+        .map(|(app_name, duration)| DailyAppUsage {
+            date: local_date,
+            app_name,
+            total_usage: duration.max(0) as u64,
+        })
+        .collect();
+    let stats = DailyUsageStats {
+        date: local_date,
+        total_usage: usage.iter().map(|usage| usage.total_usage).sum(),
+    };
+    sink.write(WriteOp::ReplaceDailyAppUsage {
+        date: local_date,
+        usage,
+    })?;
+    sink.write(WriteOp::ReplaceHourlyAppUsage {
+        date: local_date,
+        usage: collect_hourly_app_usage(conn, local_date, day_start_hour)?,
+    })?;
+    sink.write(WriteOp::UpsertDailyFocusStats {
+        date: local_date,
+        stats: collect_focus_stats(conn, local_date, day_start_hour)?,
+    })?;
+    sink.write(WriteOp::UpsertDailyUsageStats(stats))
+}
+
+/// Recompute the aggregates of `days`, then mark those before the start of the latest
+/// session as finalized: the tracker only extends the latest session, so they can't
+/// change anymore.
+fn recompute_days(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    days: impl IntoIterator<Item = NaiveDate>,
+    day_start_hour: u32,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let open_day = repo::latest_session(conn)?
+        .map(|session| wall_clock_date(session.start_ms, session.utc_offset, day_start_hour));
+    let now = Utc::now().timestamp_millis();
+    let mut count = 0;
+    for date in days {
+        recompute_day(conn, sink, date, day_start_hour)?;
+        if open_day.is_some_and(|open_day| date < open_day) {
+            sink.write(WriteOp::FinalizeDay {
+                date,
+                finalized_ms: now,
+            })?;
+        }
+        count += 1;
+    }
+    sink.flush()?;
+    Ok(count)
+}
+
+/// Recompute the days with app sessions or logs which aren't finalized yet, such as those the
+/// app was closed or asleep at the end of. Returns the number of days recomputed.
+pub fn finalize_past_days(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    day_start_hour: u32,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let days = repo::unfinalized_days(conn, day_start_hour)?;
+    let count = recompute_days(conn, sink, days, day_start_hour)?;
     logging!(
         debug,
         Type::Statistics,
         false,
-        "Collect app usage duration: time range from {} to {}",
-        start_of_day,
-        end_of_day
+        "Recomputed {} unfinalized days",
+        count
     );
+    Ok(count)
+}
+
+/// Drop all the aggregates and recompute them from the app sessions, after the start
+/// of the day changed. Returns the number of days recomputed.
+pub fn recompute_history(
+    conn: &Connection,
+    sink: &dyn WriteSink,
+    day_start_hour: u32,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    sink.write(WriteOp::ClearAggregates)?;
+    sink.flush()?;
+    finalize_past_days(conn, sink, day_start_hour)
+}
+
+/// A span of time spent in one window, in epoch milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageSegment {
+    pub start: i64,
+    pub end: i64,
+    /// The UTC offset in seconds when the segment started
+    pub utc_offset: i32,
+    pub app_name: String,
+    pub title: String,
+}
+
+impl UsageSegment {
+    /// The duration in seconds
+    pub fn duration(&self) -> i64 {
+        (self.end - self.start) / 1000
+    }
+}
 
-    let sql = format!(
-        "SELECT * FROM {} WHERE time BETWEEN '{}' AND '{}'",
-        TABLE::APP_USAGE_LOGS,
-        start_of_day,
-        end_of_day
+/// Read the app sessions during { local_date }, by the wall clock at the time they
+/// started. Sessions spanning midnight are split at the day bounds.
+fn collect_usage_segments(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<Vec<UsageSegment>, rusqlite::Error> {
+    logging!(
+        debug,
+        Type::Statistics,
+        false,
+        "Collect usage segments of {}",
+        local_date
     );
-    let mut stmt = conn.prepare(&sql)?;
-    let mut rows = stmt.query([])?;
-
-    // Get the first row as 'pre'
-    let mut pre_time: Option<String> = None;
-    let mut pre_name: Option<String> = None;
-    while let Some(row) = rows.next()? {
-        pre_time = Some(row.get::<_, String>(1)?);
-        pre_name = Some(row.get::<_, String>(2)?);
-        logging!(
-            debug,
-            Type::Statistics,
-            false,
-            "First log entry: time = {}, app = {}",
-            pre_time.as_ref().unwrap(),
-            pre_name.as_ref().unwrap()
+
+    let (start_of_day, end_of_day) = wall_clock_day_bounds(local_date, day_start_hour)?;
+    let mut segments = Vec::new();
+    for session in repo::sessions_during(conn, local_date, day_start_hour)? {
+        let offset_ms = session.utc_offset as i64 * 1000;
+        push_segment(
+            &mut segments,
+            session.start_ms.max(start_of_day - offset_ms),
+            session.end_ms.min(end_of_day - offset_ms),
+            session.utc_offset,
+            &session.app_name,
+            &session.title,
         );
+    }
+    Ok(segments)
+}
 
-        // In the case that the result of query is none
-        if pre_name.is_none() {
-            return Ok(HashMap::new());
-        }
+fn push_segment(
+    segments: &mut Vec<UsageSegment>,
+    start: i64,
+    end: i64,
+    utc_offset: i32,
+    app_name: &str,
+    title: &str,
+) {
+    // Don't display applications which R_IGNORE_APP_LIST contains
+    if R_IGNORE_APP_LIST.contains(&app_name) || end <= start {
+        return;
+    }
+    segments.push(UsageSegment {
+        start,
+        end,
+        utc_offset,
+        app_name: app_name.to_string(),
+        title: title.to_string(),
+    });
+}
 
-        // Don't display applications which R_IGNORE_APP_LIST contains
-        if R_IGNORE_APP_LIST.contains(&pre_name.as_ref().unwrap().as_str()) {
-            continue;
+/// Get all app usage durations that occurred today
+pub fn collect_app_usage_duration(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<HashMap<String, i64>, rusqlite::Error> {
+    let mut result: HashMap<String, i64> = HashMap::new();
+    for segment in collect_usage_segments(conn, local_date, day_start_hour)? {
+        *result.entry(segment.app_name.clone()).or_default() += segment.duration();
+    }
+    logging!(
+        debug,
+        Type::Statistics,
+        false,
+        "Total app usage duration: {}",
+        jsonify(&result)
+    );
+    Ok(result)
+}
+
+/// Get the usage durations of each app on { local_date }, split by the hour of the wall
+/// clock.
+pub fn collect_hourly_app_usage(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<Vec<HourlyAppUsage>, rusqlite::Error> {
+    const HOUR_MS: i64 = 3600 * 1000;
+    // Milliseconds, by hour and app
+    let mut durations: HashMap<(u32, String), i64> = HashMap::new();
+    for segment in collect_usage_segments(conn, local_date, day_start_hour)? {
+        let offset_ms = segment.utc_offset as i64 * 1000;
+        let (mut start, end) = (segment.start + offset_ms, segment.end + offset_ms);
+        while start < end {
+            let hour_end = (start.div_euclid(HOUR_MS) + 1) * HOUR_MS;
+            let hour = start.div_euclid(HOUR_MS).rem_euclid(24) as u32;
+            *durations
+                .entry((hour, segment.app_name.clone()))
+                .or_default() += hour_end.min(end) - start;
+            start = hour_end;
         }
+    }
+    let mut result: Vec<HourlyAppUsage> = durations
+        .into_iter()
+        .map(|((hour, app_name), duration)| HourlyAppUsage {
+            date: local_date,
+            hour,
+            app_name,
+            total_usage: (duration / 1000) as u64,
+        })
+        .collect();
+    result.sort_by(|a, b| (a.hour, &a.app_name).cmp(&(b.hour, &b.app_name)));
+    Ok(result)
+}
 
-        break;
+/// Get the usage durations of each window title, grouped by app, that occurred on { local_date }
+pub fn collect_title_usage_duration(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<HashMap<String, HashMap<String, i64>>, rusqlite::Error> {
+    let mut result: HashMap<String, HashMap<String, i64>> = HashMap::new();
+    for segment in collect_usage_segments(conn, local_date, day_start_hour)? {
+        let duration = segment.duration();
+        *result
+            .entry(segment.app_name)
+            .or_default()
+            .entry(segment.title)
+            .or_default() += duration;
     }
+    Ok(result)
+}
 
-    // TO-DO: if PC is sleepping, the sleep event may not be recorded
-    // Traverse from the second row onwards
-    let mut result: HashMap<String, i64> = HashMap::new();
-    while let Some(row) = rows.next()? {
-        let cur_time = row.get::<_, String>(1)?;
-        let cur_name = row.get::<_, String>(2)?;
+/// Get how fragmented the usage of { local_date } was.
+///
+/// A focus session is a stretch in one app: segments of the same app are joined across
+/// title changes and gaps up to `SUSPEND_GAP_SECS`, longer gaps end it.
+pub fn collect_focus_stats(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<FocusStats, rusqlite::Error> {
+    let mut segments = collect_usage_segments(conn, local_date, day_start_hour)?;
+    segments.sort_by_key(|segment| segment.start);
+    Ok(focus_stats(&segments))
+}
 
-        if R_IGNORE_APP_LIST.contains(&pre_name.as_ref().unwrap().as_str()) {
-            pre_time = Some(cur_time);
-            pre_name = Some(cur_name);
-            continue;
+fn focus_stats(segments: &[UsageSegment]) -> FocusStats {
+    let mut stats = FocusStats::default();
+    // Seconds of each focus session
+    let mut durations: Vec<u64> = Vec::new();
+    let mut current: Option<(&str, i64, i64)> = None;
+    for segment in segments {
+        if let Some((app_name, start, end)) = current {
+            if app_name == segment.app_name && segment.start - end <= SUSPEND_GAP_SECS * 1000 {
+                current = Some((app_name, start, end.max(segment.end)));
+                continue;
+            }
+            durations.push(((end - start) / 1000) as u64);
+            if app_name != segment.app_name {
+                stats.app_switches += 1;
+            }
         }
+        current = Some((&segment.app_name, segment.start, segment.end));
+    }
+    if let Some((_, start, end)) = current {
+        durations.push(((end - start) / 1000) as u64);
+    }
+
+    durations.sort_unstable();
+    stats.longest_session = durations.last().copied().unwrap_or(0);
+    stats.median_session = match durations.len() {
+        0 => 0,
+        n if n % 2 == 0 => (durations[n / 2 - 1] + durations[n / 2]) / 2,
+        n => durations[n / 2],
+    };
+    stats.short_sessions = durations
+        .iter()
+        .filter(|duration| **duration < FOCUS_SHORT_SESSION_SECS)
+        .count() as u32;
+    stats
+}
+
+/// A stretch of time spent in one app on the timeline, in epoch milliseconds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimelineSegment {
+    pub start: i64,
+    pub end: i64,
+    pub app_name: String,
+    /// None if the merged segments had different titles
+    pub title: Option<String>,
+}
+
+/// Get the timeline of { local_date }, ordered by time. Segments of the same app with a
+/// short gap are merged, and those shorter than `TIMELINE_MIN_SEGMENT_SECS` dropped.
+pub fn collect_day_timeline(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<Vec<TimelineSegment>, rusqlite::Error> {
+    let mut segments = collect_usage_segments(conn, local_date, day_start_hour)?;
+    segments.sort_by_key(|segment| segment.start);
+    let timeline = merge_adjacent(segments.into_iter().map(|segment| TimelineSegment {
+        start: segment.start,
+        end: segment.end,
+        app_name: segment.app_name,
+        title: Some(segment.title),
+    }));
+    // Merge again, the dropped segments may have split runs of an app
+    Ok(merge_adjacent(timeline.into_iter().filter(|segment| {
+        segment.end - segment.start >= TIMELINE_MIN_SEGMENT_SECS * 1000
+    })))
+}
 
-        if let (Ok(pre_dt), Ok(cur_dt)) = (
-            chrono::NaiveDateTime::parse_from_str(&pre_time.as_ref().unwrap(), "%Y-%m-%d %H:%M:%S"),
-            chrono::NaiveDateTime::parse_from_str(&cur_time, "%Y-%m-%d %H:%M:%S"),
-        ) {
-            let duration = cur_dt.signed_duration_since(pre_dt).num_seconds();
-            result
-                .entry(pre_name.as_ref().unwrap().clone())
-                .and_modify(|v| *v += duration)
-                .or_insert(duration);
+/// Merge each segment into the one before it, if it's of the same app and starts at
+/// most `TIMELINE_MERGE_GAP_SECS` after it ends
+fn merge_adjacent(segments: impl IntoIterator<Item = TimelineSegment>) -> Vec<TimelineSegment> {
+    let mut merged: Vec<TimelineSegment> = Vec::new();
+    for segment in segments {
+        if let Some(last) = merged.last_mut()
+            && last.app_name == segment.app_name
+            && segment.start - last.end <= TIMELINE_MERGE_GAP_SECS * 1000
+        {
+            last.end = last.end.max(segment.end);
+            if last.title != segment.title {
+                last.title = None;
+            }
+            continue;
         }
-        pre_time = Some(cur_time);
-        pre_name = Some(cur_name);
+        merged.push(segment);
     }
+    merged
+}
+
+/// Get the usage durations of each window title within specific range, grouped by app
+///
+/// ## Params
+///
+/// - `app_name`: Only collect titles of this app if provided
+fn get_title_usage_duration(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    app_name: Option<&str>,
+    day_start_hour: u32,
+) -> Result<HashMap<String, HashMap<String, u64>>, rusqlite::Error> {
     logging!(
         debug,
         Type::Statistics,
         false,
-        "Total app usage duration: {}",
-        jsonify(&result)
+        "Get title usage duration: time range from {} to {}, app: {:?}",
+        start_date,
+        end_date,
+        app_name
     );
+
+    let mut result: HashMap<String, HashMap<String, u64>> = HashMap::new();
+    for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+        for (app, titles) in collect_title_usage_duration(conn, date, day_start_hour)? {
+            if app_name.is_some_and(|name| name != app) {
+                continue;
+            }
+            let entry = result.entry(app).or_default();
+            for (title, duration) in titles {
+                *entry.entry(title).or_default() += duration.max(0) as u64;
+            }
+        }
+    }
     Ok(result)
 }
 
@@ -145,82 +458,27 @@ fn get_app_usage_duration(
         end_date
     );
 
-    let sql = format!(
-        "SELECT * FROM {} WHERE date BETWEEN '{}' AND '{}'",
-        TABLE::DAILY_APP_USAGE,
-        start_date,
-        end_date
-    );
-    let mut stmt = conn.prepare(&sql)?;
-    let mut rows = stmt.query([])?;
-
     let mut result: HashMap<String, HashMap<String, u64>> = HashMap::new();
-
-    while let Some(row) = rows.next()? {
-        let date = row.get::<_, String>(1)?;
-        let app_name = row.get::<_, String>(2)?;
-        let total_usage = row.get::<_, u64>(3)?;
-        if R_IGNORE_APP_LIST.contains(&app_name.as_str()) {
+    for usage in repo::daily_app_usage_between(conn, start_date, end_date)? {
+        if R_IGNORE_APP_LIST.contains(&usage.app_name.as_str()) {
             continue;
         }
         result
-            .entry(date)
-            .or_insert_with(HashMap::new)
-            .insert(app_name, total_usage);
+            .entry(usage.date.to_string())
+            .or_default()
+            .insert(usage.app_name, usage.total_usage);
     }
 
     Ok(result)
 }
 
-/// Get all app usage durations that occurred today
-fn get_daily_usage_duration(conn: &Connection) -> Result<u64, rusqlite::Error> {
-    let local_today = get_local_date();
-
-    let sql = format!(
-        "SELECT * FROM {} WHERE date = '{}'",
-        TABLE::DAILY_APP_USAGE,
-        local_today
-    );
-    let mut stmt = conn.prepare(&sql)?;
-    let mut rows = stmt.query([])?;
-
-    // Summation
-    let mut total_duration = 0;
-    while let Some(row) = rows.next()? {
-        total_duration += row.get::<_, u64>(3)?;
-    }
-
-    Ok(total_duration)
-}
-
-/// Obtain the starting and ending times of the { local_date }
-fn get_local_day_start_end_in_utc(local_date: NaiveDate) -> (String, String) {
-    let local_start = local_date.and_hms_opt(0, 0, 0).unwrap();
-    let local_end = local_date.and_hms_opt(23, 59, 59).unwrap();
-    // Convert to UTC
-    let utc_start = Local
-        .from_local_datetime(&local_start)
-        .unwrap()
-        .with_timezone(&Utc);
-    let utc_end = Local
-        .from_local_datetime(&local_end)
-        .unwrap()
-        .with_timezone(&Utc);
-    let start_of_day = utc_start.format("%Y-%m-%d %H:%M:%S").to_string();
-    let end_of_day = utc_end.format("%Y-%m-%d %H:%M:%S").to_string();
-    logging!(
-        debug,
-        Type::Statistics,
-        false,
-        "Query range from {} to {}",
-        start_of_day,
-        end_of_day
-    );
-    (start_of_day, end_of_day)
+/// The hour local days start at, from the config
+fn day_start_hour() -> u32 {
+    ConfigManager::global().get().day_start_hour
 }
 
 fn get_local_date() -> NaiveDate {
-    Local::now().date_naive()
+    local_date(day_start_hour())
 }
 
 /// Obtain the date n days ago in UTC
@@ -232,7 +490,9 @@ fn get_recall_date_in_utc(start: NaiveDate, n: u64) -> NaiveDate {
 pub fn get_app_usage_duration_last_n_days(
     n: u64,
 ) -> Result<HashMap<String, HashMap<String, u64>>, String> {
-    let conn = DbManager::global().get().lock();
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
     let now_date = get_local_date();
     let start_date = now_date - Days::new(n);
     match get_app_usage_duration(&conn, start_date, now_date) {
@@ -246,7 +506,9 @@ pub fn get_app_usage_duration_range(
     start_date: &str,
     end_date: &str,
 ) -> Result<HashMap<String, HashMap<String, u64>>, String> {
-    let conn = DbManager::global().get().lock();
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
     let utc_start_date = NaiveDate::parse_from_str(start_date, "%Y-%m-%d").unwrap();
     let utc_end_date = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").unwrap();
     match get_app_usage_duration(&conn, utc_start_date, utc_end_date) {
@@ -255,33 +517,65 @@ pub fn get_app_usage_duration_range(
     }
 }
 
+#[tauri::command]
+pub fn get_title_usage_duration_last_n_days(
+    n: u64,
+    app_name: Option<String>,
+) -> Result<HashMap<String, HashMap<String, u64>>, String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    let now_date = get_local_date();
+    let start_date = now_date - Days::new(n);
+    match get_title_usage_duration(
+        &conn,
+        start_date,
+        now_date,
+        app_name.as_deref(),
+        day_start_hour(),
+    ) {
+        Ok(mp) => Ok(mp),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub fn get_title_usage_duration_range(
+    start_date: &str,
+    end_date: &str,
+    app_name: Option<String>,
+) -> Result<HashMap<String, HashMap<String, u64>>, String> {
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    let start_date =
+        NaiveDate::parse_from_str(start_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let end_date = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    match get_title_usage_duration(
+        &conn,
+        start_date,
+        end_date,
+        app_name.as_deref(),
+        day_start_hour(),
+    ) {
+        Ok(mp) => Ok(mp),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
 #[tauri::command]
 pub fn get_daily_usage_duration_last_n_days(n: u64) -> Result<HashMap<String, u64>, String> {
-    let conn = DbManager::global().get().lock();
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
     let local_date_in_utc = get_local_date();
     let start_date_in_utc = get_recall_date_in_utc(local_date_in_utc, n);
-    let sql = format!(
-        "SELECT * FROM {} WHERE date BETWEEN '{}' AND '{}'",
-        TABLE::DAILY_USAGE_STATS,
-        start_date_in_utc,
-        local_date_in_utc
-    );
-    let mut stmt = conn
-        .prepare(&sql)
-        .map_err(|e| format!("SQL prepare error: {}", e))?;
-    let mut rows = stmt
-        .query([])
-        .map_err(|e| format!("SQL query error: {}", e))?;
-    let mut result = HashMap::new();
-    while let Some(row) = rows.next().map_err(|e| format!("Rows next error: {}", e))? {
-        let date = row
-            .get::<_, String>(1)
-            .map_err(|e| format!("Row get error: {}", e))?;
-        let duration = row
-            .get::<_, u64>(2)
-            .map_err(|e| format!("Row get error: {}", e))?;
-        result.insert(date, duration);
-    }
+    let result: HashMap<String, u64> =
+        repo::daily_totals(&conn, start_date_in_utc, local_date_in_utc)
+            .map_err(|e| format!("SQL query error: {}", e))?
+            .into_iter()
+            .map(|stats| (stats.date.to_string(), stats.total_usage))
+            .collect();
     logging!(
         debug,
         Type::Statistics,
@@ -294,9 +588,13 @@ pub fn get_daily_usage_duration_last_n_days(n: u64) -> Result<HashMap<String, u6
 
 #[tauri::command]
 pub fn refresh_data() -> Result<(), String> {
-    let conn = DbManager::global().get().lock();
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    let writer = DbWriter::global();
     let err_msg = "Failed to refresh data";
-    if let Err(e) = update_daily_app_usage(&conn) {
+    let day_start_hour = day_start_hour();
+    if let Err(e) = update_daily_app_usage(&conn, writer, day_start_hour) {
         logging!(
             error,
             Type::Statistics,
@@ -306,7 +604,7 @@ pub fn refresh_data() -> Result<(), String> {
         );
         return Err(format!("{}: {}", err_msg, e));
     }
-    if let Err(e) = update_daily_usage_stats(&conn) {
+    if let Err(e) = update_daily_usage_stats(&conn, writer, day_start_hour) {
         logging!(
             error,
             Type::Statistics,
@@ -319,9 +617,200 @@ pub fn refresh_data() -> Result<(), String> {
     Ok(())
 }
 
+/// Get the usage, in seconds, of each hour of each weekday from `start_date` to
+/// `end_date`, both included. Rows are weekdays from Monday, columns hours of the wall
+/// clock.
+///
+/// ## Params
+///
+/// - `app_name`: Only count this app if provided
+#[tauri::command]
+pub fn get_hourly_heatmap(
+    start_date: &str,
+    end_date: &str,
+    app_name: Option<String>,
+) -> Result<Vec<Vec<u64>>, String> {
+    let start_date =
+        NaiveDate::parse_from_str(start_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let end_date = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    match repo::hourly_totals(&conn, start_date, end_date, app_name.as_deref()) {
+        Ok(totals) => Ok(weekday_heatmap(totals)),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
+fn weekday_heatmap(totals: Vec<(NaiveDate, u32, u64)>) -> Vec<Vec<u64>> {
+    let mut heatmap = vec![vec![0; 24]; 7];
+    for (date, hour, total_usage) in totals {
+        heatmap[date.weekday().num_days_from_monday() as usize][hour as usize] += total_usage;
+    }
+    heatmap
+}
+
+/// Get the focus stats of each day from `start_date` to `end_date`, both included, by
+/// date
+#[tauri::command]
+pub fn get_focus_stats_range(
+    start_date: &str,
+    end_date: &str,
+) -> Result<HashMap<String, FocusStats>, String> {
+    let start_date =
+        NaiveDate::parse_from_str(start_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let end_date = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    match repo::daily_focus_stats_between(&conn, start_date, end_date) {
+        Ok(stats) => Ok(stats
+            .into_iter()
+            .map(|(date, stats)| (date.to_string(), stats))
+            .collect()),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
+/// Get the ordered segments of app usage on `date`, to draw a timeline of it
+#[tauri::command]
+pub fn get_day_timeline(date: &str) -> Result<Vec<TimelineSegment>, String> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    match collect_day_timeline(&conn, date, day_start_hour()) {
+        Ok(timeline) => Ok(timeline),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
+/// Rank the apps by their usage from `start_date` to `end_date`, both included, against
+/// the period of the same length right before.
+fn app_ranking(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    limit: u32,
+) -> Result<Vec<AppRank>, Box<dyn std::error::Error>> {
+    if end_date < start_date {
+        return Err(format!("{} is before {}", end_date, start_date).into());
+    }
+    let days = Days::new((end_date - start_date).num_days() as u64 + 1);
+    let previous_end = start_date - Days::new(1);
+    let previous_start = start_date - days;
+    Ok(repo::app_ranking(
+        conn,
+        start_date,
+        end_date,
+        previous_start,
+        previous_end,
+        limit,
+    )?)
+}
+
+/// Get the `limit` most used apps from `start_date` to `end_date`, both included, with
+/// their share of the total usage and the change from the period of the same length
+/// before.
+#[tauri::command]
+pub fn get_app_ranking(
+    start_date: &str,
+    end_date: &str,
+    limit: u32,
+) -> Result<Vec<AppRank>, String> {
+    let start_date =
+        NaiveDate::parse_from_str(start_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let end_date = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    match app_ranking(&conn, start_date, end_date, limit) {
+        Ok(ranking) => Ok(ranking),
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
+/// Recompute the aggregates from `start_date` to `end_date`, both included, to repair
+/// them by hand.
+#[tauri::command]
+pub fn recompute_range(start_date: &str, end_date: &str) -> Result<(), String> {
+    let start_date =
+        NaiveDate::parse_from_str(start_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let end_date = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    let conn = DbManager::global()
+        .get()
+        .map_err(|e| format!("Error occured: {}", e))?;
+    let days = start_date.iter_days().take_while(|d| *d <= end_date);
+    match recompute_days(&conn, DbWriter::global(), days, day_start_hour()) {
+        Ok(count) => {
+            logging!(
+                info,
+                Type::Statistics,
+                false,
+                "Recomputed {} days from {} to {}",
+                count,
+                start_date,
+                end_date
+            );
+            Ok(())
+        }
+        Err(e) => Err(format!("Error occured: {}", e)),
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
+    use crate::core::activity::ActivityEvent;
+    use crate::core::tracker::Tracker;
+    use crate::utils::repo::SessionRow;
+    use crate::utils::test::memory_db;
+    use chrono::{DateTime, Local, TimeZone};
+
+    fn at(date: NaiveDate, hms: (u32, u32, u32)) -> DateTime<Utc> {
+        let local = date.and_hms_opt(hms.0, hms.1, hms.2).unwrap();
+        Local
+            .from_local_datetime(&local)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// Drive a tracker through focus changes, with a heartbeat every minute while the
+    /// PC is awake. `awake` lists the periods between suspends, both bounds included.
+    fn track(
+        conn: &Connection,
+        focus: &[(DateTime<Utc>, &str)],
+        awake: &[(DateTime<Utc>, DateTime<Utc>)],
+    ) {
+        let mut events: Vec<(DateTime<Utc>, Option<&str>)> = focus
+            .iter()
+            .map(|(time, app_name)| (*time, Some(*app_name)))
+            .collect();
+        for (from, to) in awake {
+            let mut time = *from;
+            while time <= *to {
+                events.push((time, None));
+                time += chrono::Duration::minutes(1);
+            }
+        }
+        // A focus change comes before the heartbeat of the same time
+        events.sort_by_key(|(time, app_name)| (*time, app_name.is_none()));
+        let mut tracker = Tracker::new();
+        for (time, app_name) in events {
+            match app_name {
+                Some(app_name) => {
+                    let event = ActivityEvent::Focus {
+                        time,
+                        app_name: app_name.to_string(),
+                        title: String::new(),
+                        source: "mock",
+                    };
+                    tracker.handle(conn, event).unwrap();
+                }
+                None => tracker.heartbeat(conn, time).unwrap(),
+            }
+        }
+    }
 
     #[test]
     pub fn init_logger() {
@@ -351,4 +840,412 @@ pub mod test {
             jsonify(&mp)
         );
     }
+
+    #[test]
+    pub fn test_suspend_gap_not_credited() {
+        let conn = memory_db();
+        let date = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let at = |h, m| at(date, (h, m, 0));
+        // Suspended over lunch, the window is still focused after resume
+        track(
+            &conn,
+            &[(at(9, 0), "Visual Studio Code"), (at(13, 30), "Firefox")],
+            &[(at(9, 0), at(12, 0)), (at(13, 0), at(14, 0))],
+        );
+
+        let usage = collect_app_usage_duration(&conn, date, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&((3 * 60 + 30) * 60)));
+        assert_eq!(usage.get("Firefox"), Some(&(30 * 60)));
+    }
+
+    #[test]
+    pub fn test_sleep_spanning_midnight() {
+        let conn = memory_db();
+        let day1 = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let day2 = day1.succ_opt().unwrap();
+        // Asleep from 23:50 until 07:00 the next morning
+        track(
+            &conn,
+            &[
+                (at(day1, (23, 0, 0)), "Visual Studio Code"),
+                (at(day2, (7, 0, 0)), "Firefox"),
+            ],
+            &[
+                (at(day1, (23, 0, 0)), at(day1, (23, 50, 0))),
+                (at(day2, (7, 0, 0)), at(day2, (8, 0, 0))),
+            ],
+        );
+
+        let usage = collect_app_usage_duration(&conn, day1, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(50 * 60)));
+        assert_eq!(usage.values().sum::<i64>(), 50 * 60);
+
+        let usage = collect_app_usage_duration(&conn, day2, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), None);
+        assert_eq!(usage.get("Firefox"), Some(&(60 * 60)));
+
+        let segments = collect_usage_segments(&conn, day2, 0).unwrap();
+        assert!(
+            segments
+                .iter()
+                .all(|s| s.start >= at(day2, (7, 0, 0)).timestamp_millis())
+        );
+    }
+
+    /// Insert a session at a fixed time, whatever the time zone running the test
+    fn session(conn: &Connection, start: &str, minutes: i64, utc_offset: i32, app_name: &str) {
+        let start = DateTime::parse_from_rfc3339(start)
+            .unwrap()
+            .timestamp_millis();
+        let session = SessionRow {
+            id: 0,
+            start_ms: start,
+            end_ms: start + minutes * 60 * 1000,
+            utc_offset,
+            app_name: app_name.to_string(),
+            title: String::new(),
+            source: "mock".to_string(),
+        };
+        repo::insert_session(conn, &session).unwrap();
+    }
+
+    #[test]
+    pub fn test_day_bucketing_uses_recorded_offset() {
+        let conn = memory_db();
+        // 23:30 on Sep 1 in UTC+8
+        session(
+            &conn,
+            "2025-09-01T15:30:00Z",
+            20,
+            8 * 3600,
+            "Visual Studio Code",
+        );
+        // 00:30 on Sep 2 in UTC+8
+        session(&conn, "2025-09-01T16:30:00Z", 10, 8 * 3600, "Firefox");
+        // 22:00 on Sep 1 after travelling to UTC-5, later in UTC than both above
+        session(&conn, "2025-09-02T03:00:00Z", 30, -5 * 3600, "Terminal");
+
+        let day1 = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let usage = collect_app_usage_duration(&conn, day1, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(20 * 60)));
+        assert_eq!(usage.get("Terminal"), Some(&(30 * 60)));
+        assert_eq!(usage.get("Firefox"), None);
+
+        let usage = collect_app_usage_duration(&conn, day1.succ_opt().unwrap(), 0).unwrap();
+        assert_eq!(usage.get("Firefox"), Some(&(10 * 60)));
+        assert_eq!(usage.len(), 1);
+    }
+
+    #[test]
+    pub fn test_finalize_past_days() {
+        let conn = memory_db();
+        let day1 = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let day2 = day1.succ_opt().unwrap();
+        session(&conn, "2025-09-01T22:00:00Z", 60, 0, "Visual Studio Code");
+        session(&conn, "2025-09-02T09:00:00Z", 30, 0, "Firefox");
+        // Left partial by the 120s tick before the app was closed
+        let partial = DailyAppUsage {
+            date: day1,
+            app_name: "Terminal".to_string(),
+            total_usage: 60,
+        };
+        repo::upsert_daily_app_usage(&conn, &partial).unwrap();
+
+        assert_eq!(finalize_past_days(&conn, &conn, 0).unwrap(), 2);
+        let usage = repo::daily_app_usage_between(&conn, day1, day1).unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].app_name, "Visual Studio Code");
+        assert_eq!(usage[0].total_usage, 60 * 60);
+        let totals = repo::daily_totals(&conn, day1, day2).unwrap();
+        assert_eq!(totals[0].total_usage, 60 * 60);
+        assert_eq!(totals[1].total_usage, 30 * 60);
+
+        // The day of the latest session may still grow, it's left unfinalized
+        assert_eq!(repo::unfinalized_days(&conn, 0).unwrap(), vec![day2]);
+        assert_eq!(finalize_past_days(&conn, &conn, 0).unwrap(), 1);
+    }
+
+    #[test]
+    pub fn test_sessions_split_at_midnight() {
+        let conn = memory_db();
+        let day1 = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let day2 = day1.succ_opt().unwrap();
+        let day3 = day2.succ_opt().unwrap();
+        // 23:30 on Sep 1 to 00:45 on Sep 2 in UTC+8
+        session(
+            &conn,
+            "2025-09-01T15:30:00Z",
+            75,
+            8 * 3600,
+            "Visual Studio Code",
+        );
+        // 23:00 on Sep 2 to 01:00 on Sep 4 in UTC-5, over all of Sep 3
+        session(&conn, "2025-09-03T04:00:00Z", 26 * 60, -5 * 3600, "Firefox");
+        // 22:00 on Sep 3 in UTC+8, ending exactly at midnight
+        session(&conn, "2025-09-03T14:00:00Z", 120, 8 * 3600, "Terminal");
+
+        let usage = collect_app_usage_duration(&conn, day1, 0).unwrap();
+        assert_eq!(
+            usage,
+            HashMap::from([("Visual Studio Code".into(), 30 * 60)])
+        );
+
+        let usage = collect_app_usage_duration(&conn, day2, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(45 * 60)));
+        assert_eq!(usage.get("Firefox"), Some(&(60 * 60)));
+
+        let usage = collect_app_usage_duration(&conn, day3, 0).unwrap();
+        assert_eq!(usage.get("Firefox"), Some(&(24 * 3600)));
+        assert_eq!(usage.get("Terminal"), Some(&(120 * 60)));
+
+        let usage = collect_app_usage_duration(&conn, day3.succ_opt().unwrap(), 0).unwrap();
+        assert_eq!(usage, HashMap::from([("Firefox".into(), 60 * 60)]));
+
+        // The split segments add up to the whole sessions
+        let total: i64 = (0..4)
+            .flat_map(|n| collect_usage_segments(&conn, day1 + Days::new(n), 0).unwrap())
+            .map(|segment| segment.duration())
+            .sum();
+        assert_eq!(total, (75 + 26 * 60 + 120) * 60);
+        assert_eq!(repo::unfinalized_days(&conn, 0).unwrap().len(), 4);
+    }
+
+    #[test]
+    pub fn test_day_start_hour() {
+        let conn = memory_db();
+        let day1 = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let day2 = day1.succ_opt().unwrap();
+        // 23:00 on Sep 1 to 02:00 on Sep 2 in UTC+8
+        session(
+            &conn,
+            "2025-09-01T15:00:00Z",
+            3 * 60,
+            8 * 3600,
+            "Visual Studio Code",
+        );
+        // 05:00 on Sep 2 in UTC+8
+        session(&conn, "2025-09-01T21:00:00Z", 60, 8 * 3600, "Firefox");
+
+        let usage = collect_app_usage_duration(&conn, day1, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(60 * 60)));
+        let usage = collect_app_usage_duration(&conn, day1, 4).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(3 * 60 * 60)));
+        let usage = collect_app_usage_duration(&conn, day2, 4).unwrap();
+        assert_eq!(usage, HashMap::from([("Firefox".into(), 60 * 60)]));
+
+        finalize_past_days(&conn, &conn, 0).unwrap();
+        assert_eq!(recompute_history(&conn, &conn, 4).unwrap(), 2);
+        let usage = repo::daily_app_usage_between(&conn, day1, day2).unwrap();
+        let usage: Vec<_> = usage
+            .iter()
+            .map(|u| (u.date, u.app_name.as_str(), u.total_usage))
+            .collect();
+        assert_eq!(
+            usage,
+            vec![
+                (day1, "Visual Studio Code", 3 * 60 * 60),
+                (day2, "Firefox", 60 * 60)
+            ]
+        );
+        assert_eq!(repo::unfinalized_days(&conn, 4).unwrap(), vec![day2]);
+    }
+
+    #[test]
+    pub fn test_hourly_heatmap() {
+        let conn = memory_db();
+        // Monday 08:40 to 10:10 in UTC+8
+        session(
+            &conn,
+            "2025-09-01T00:40:00Z",
+            90,
+            8 * 3600,
+            "Visual Studio Code",
+        );
+        // Monday 09:30 in UTC+8
+        session(&conn, "2025-09-01T01:30:00Z", 15, 8 * 3600, "Firefox");
+        // Tuesday 23:30 to Wednesday 00:30 in UTC+8
+        session(
+            &conn,
+            "2025-09-02T15:30:00Z",
+            60,
+            8 * 3600,
+            "Visual Studio Code",
+        );
+        finalize_past_days(&conn, &conn, 0).unwrap();
+
+        let monday = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let hourly = collect_hourly_app_usage(&conn, monday, 0).unwrap();
+        let hourly: Vec<_> = hourly
+            .iter()
+            .map(|u| (u.hour, u.app_name.as_str(), u.total_usage))
+            .collect();
+        assert_eq!(
+            hourly,
+            vec![
+                (8, "Visual Studio Code", 20 * 60),
+                (9, "Firefox", 15 * 60),
+                (9, "Visual Studio Code", 60 * 60),
+                (10, "Visual Studio Code", 10 * 60),
+            ]
+        );
+
+        let end = monday + Days::new(6);
+        let heatmap = weekday_heatmap(repo::hourly_totals(&conn, monday, end, None).unwrap());
+        assert_eq!(heatmap.len(), 7);
+        assert_eq!(heatmap[0][9], 75 * 60);
+        assert_eq!(heatmap[1][23], 30 * 60);
+        assert_eq!(heatmap[2][0], 30 * 60);
+        let total: u64 = heatmap.iter().flatten().sum();
+        assert_eq!(total, (90 + 15 + 60) * 60);
+
+        let totals = repo::hourly_totals(&conn, monday, end, Some("Firefox")).unwrap();
+        assert_eq!(totals, vec![(monday, 9, 15 * 60)]);
+    }
+
+    #[test]
+    pub fn test_app_ranking() {
+        let conn = memory_db();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 9, day).unwrap();
+        let usage = |day, app_name: &str, total_usage| {
+            let usage = DailyAppUsage {
+                date: date(day),
+                app_name: app_name.to_string(),
+                total_usage,
+            };
+            repo::upsert_daily_app_usage(&conn, &usage).unwrap();
+        };
+        // The previous period, Sep 1 and 2
+        usage(1, "Firefox", 600);
+        usage(2, "Visual Studio Code", 300);
+        usage(2, "Terminal", 100);
+        // Sep 3 and 4
+        usage(3, "Visual Studio Code", 1200);
+        usage(4, "Visual Studio Code", 300);
+        usage(3, "Firefox", 500);
+        usage(4, "Terminal", 500);
+        usage(4, "Steam", 500);
+
+        let ranking = app_ranking(&conn, date(3), date(4), 3).unwrap();
+        let ranking: Vec<_> = ranking
+            .iter()
+            .map(|r| {
+                (
+                    r.rank,
+                    r.app_name.as_str(),
+                    r.total_usage,
+                    r.share,
+                    r.change,
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranking,
+            vec![
+                (1, "Visual Studio Code", 1500, 50.0, 1200),
+                (2, "Firefox", 500, 500.0 / 30.0, -100),
+                (2, "Steam", 500, 500.0 / 30.0, 500),
+            ]
+        );
+        assert_eq!(
+            app_ranking(&conn, date(3), date(4), 10).unwrap()[3].previous_usage,
+            100
+        );
+        assert!(app_ranking(&conn, date(4), date(3), 10).is_err());
+    }
+
+    #[test]
+    pub fn test_day_timeline() {
+        let conn = memory_db();
+        let time = |hms: &str| {
+            DateTime::parse_from_rfc3339(&format!("2025-09-01T{}Z", hms))
+                .unwrap()
+                .timestamp_millis()
+        };
+        let session = |start, end, app_name: &str, title: &str| {
+            let session = SessionRow {
+                id: 0,
+                start_ms: time(start),
+                end_ms: time(end),
+                utc_offset: 0,
+                app_name: app_name.to_string(),
+                title: title.to_string(),
+                source: "mock".to_string(),
+            };
+            repo::insert_session(&conn, &session).unwrap();
+        };
+        session("09:00:00", "09:10:00", "Visual Studio Code", "a.rs");
+        session("09:10:00", "09:20:00", "Visual Studio Code", "b.rs");
+        // Too short, the editor's run goes on over it
+        session("09:20:00", "09:20:20", "Firefox", "docs");
+        session("09:20:20", "09:30:00", "Visual Studio Code", "b.rs");
+        session("09:45:00", "10:00:00", "Firefox", "docs");
+        // Too far from the previous one to merge
+        session("10:05:00", "10:10:00", "Firefox", "docs");
+
+        let date = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let timeline = collect_day_timeline(&conn, date, 0).unwrap();
+        let segment = |start, end, app_name: &str, title: Option<&str>| TimelineSegment {
+            start: time(start),
+            end: time(end),
+            app_name: app_name.to_string(),
+            title: title.map(str::to_string),
+        };
+        assert_eq!(
+            timeline,
+            vec![
+                segment("09:00:00", "09:30:00", "Visual Studio Code", None),
+                segment("09:45:00", "10:00:00", "Firefox", Some("docs")),
+                segment("10:05:00", "10:10:00", "Firefox", Some("docs")),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_focus_stats() {
+        let conn = memory_db();
+        let time = |hms: &str| {
+            DateTime::parse_from_rfc3339(&format!("2025-09-01T{}Z", hms))
+                .unwrap()
+                .timestamp_millis()
+        };
+        let session = |start: &str, end: &str, app_name: &str, title: &str| {
+            let session = SessionRow {
+                id: 0,
+                start_ms: time(start),
+                end_ms: time(end),
+                utc_offset: 0,
+                app_name: app_name.to_string(),
+                title: title.to_string(),
+                source: "mock".to_string(),
+            };
+            repo::insert_session(&conn, &session).unwrap();
+        };
+        session("09:00:00", "09:05:00", "Visual Studio Code", "a.rs");
+        // A new title doesn't break the focus
+        session("09:05:00", "09:10:00", "Visual Studio Code", "b.rs");
+        session("09:10:00", "09:10:30", "Firefox", "docs");
+        session("09:10:30", "09:20:00", "Visual Studio Code", "b.rs");
+        session("09:20:00", "09:20:40", "Terminal", "cargo");
+        session("09:20:40", "09:30:00", "Visual Studio Code", "b.rs");
+        session("09:30:00", "09:40:45", "Firefox", "docs");
+        // Suspended from 09:40:45
+        session("11:00:00", "11:05:45", "Visual Studio Code", "b.rs");
+
+        let date = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        // Focus sessions of 600, 30, 570, 40, 560, 645 and 345 seconds
+        let stats = FocusStats {
+            app_switches: 6,
+            longest_session: 645,
+            median_session: 560,
+            short_sessions: 2,
+        };
+        assert_eq!(collect_focus_stats(&conn, date, 0).unwrap(), stats);
+        finalize_past_days(&conn, &conn, 0).unwrap();
+        assert_eq!(
+            repo::daily_focus_stats_between(&conn, date, date).unwrap(),
+            vec![(date, stats)]
+        );
+
+        assert_eq!(focus_stats(&[]), FocusStats::default());
+    }
 }
@@ -1,7 +1,7 @@
 use crate::logging;
 use crate::utils::logging::Type;
 use chrono::{Duration as ChronoDuration, Local, Timelike};
-use rdev::{Event, listen};
+use rdev::{Event, ListenError, listen};
 use std::sync::LazyLock;
 use tokio::runtime::Runtime;
 use tokio::time::{Duration, interval, sleep};
@@ -15,18 +15,20 @@ where
     RT.spawn(async move {
         let mut itv = interval(duration);
         loop {
+            // The first tick completes right away, so the task also runs at startup
+            itv.tick().await;
             logging!(debug, Type::Task, false, "{} executed.", id);
             task();
-            itv.tick().await;
         }
     });
 }
 
-pub fn register_event_listener<F>(_id: &'static str, task: F)
+/// Blocks while listening, returns only if the hook couldn't be installed.
+pub fn register_event_listener<F>(_id: &'static str, task: F) -> Result<(), ListenError>
 where
     F: FnMut(Event) + Send + Sync + 'static,
 {
-    listen(task).expect("Error listening for events");
+    listen(task)
 }
 
 pub fn _run_daily_task<F>(id: &'static str, task: F, hour: u32, minute: u32, second: u32)
@@ -0,0 +1,487 @@
+use crate::constants::config::DEFAULT_IDLE_THRESHOLD_SECS;
+use crate::constants::tracker::{HEARTBEAT_INTERVAL_SECS, SUSPEND_GAP_SECS};
+use crate::constants::window::{W_IGNORE_APP_LIST, WindowEvent};
+use crate::core::activity::ActivityEvent;
+use crate::logging;
+use crate::utils::config::ConfigManager;
+use crate::utils::logging::Type;
+use crate::utils::repo::{LogRow, SessionRow};
+use crate::utils::time::local_offset_secs;
+use crate::utils::window::ForegroundWindow;
+use crate::utils::writer::{WriteOp, WriteSink};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Turns the events of an activity source into app usage logs.
+///
+/// Once input events arrive, the tracker also watches for inactivity: after the idle
+/// threshold an idle marker is logged, and the foreground window is logged again on
+/// the next input.
+///
+/// Heartbeats are logged periodically, so that the time the PC spends suspended or
+/// off shows up as a gap between logs.
+///
+/// Alongside the logs, the tracker keeps the session of the logged window open in
+/// `app_sessions`: a focus change closes it and opens the next one, an idle marker
+/// closes it, and heartbeats move its end forward.
+pub struct Tracker {
+    pre_window: Option<ForegroundWindow>,
+    pre_source: &'static str,
+    session: Option<OpenSession>,
+    idle_threshold: ChronoDuration,
+    last_input: Option<DateTime<Utc>>,
+    last_logged: Option<DateTime<Utc>>,
+    last_heartbeat: Option<DateTime<Utc>>,
+    idle: bool,
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Self {
+            pre_window: None,
+            pre_source: "",
+            session: None,
+            idle_threshold: ChronoDuration::seconds(DEFAULT_IDLE_THRESHOLD_SECS as i64),
+            last_input: None,
+            last_logged: None,
+            last_heartbeat: None,
+            idle: false,
+        }
+    }
+
+    /// Set the inactivity threshold, 0 disables idle detection.
+    pub fn set_idle_threshold(&mut self, secs: u64) {
+        self.idle_threshold = ChronoDuration::seconds(secs as i64);
+    }
+
+    /// Record a single event, skipping repeated focus on the same app and title.
+    pub fn handle(
+        &mut self,
+        sink: &dyn WriteSink,
+        event: ActivityEvent,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match event {
+            ActivityEvent::Focus {
+                time,
+                app_name,
+                title,
+                source,
+            } => {
+                let window = ForegroundWindow { app_name, title };
+                if self.pre_window.as_ref() == Some(&window) {
+                    return Ok(());
+                }
+                logging!(
+                    debug,
+                    Type::Window,
+                    false,
+                    "Current window: {} - {}",
+                    window.app_name,
+                    window.title
+                );
+                self.pre_window = Some(window);
+                self.pre_source = source;
+                // The window is logged when the user is back
+                if self.idle {
+                    return Ok(());
+                }
+                self.log_window(sink, time)
+            }
+            ActivityEvent::Input { time } => {
+                self.last_input = Some(time);
+                if !self.idle {
+                    return Ok(());
+                }
+                logging!(debug, Type::Window, false, "User is back at {}", time);
+                self.idle = false;
+                self.log_window(sink, time)
+            }
+        }
+    }
+
+    /// Log an idle marker if no input arrived within the idle threshold.
+    pub fn tick(
+        &mut self,
+        sink: &dyn WriteSink,
+        now: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(last_input) = self.last_input else {
+            // The source doesn't report input, so idleness can't be told
+            return Ok(());
+        };
+        if self.idle
+            || self.idle_threshold <= ChronoDuration::zero()
+            || now - last_input < self.idle_threshold
+        {
+            return Ok(());
+        }
+        self.idle = true;
+        // The time without input is not usage, so the marker goes back to the last input
+        let idle_since = self.last_logged.map_or(last_input, |t| t.max(last_input));
+        logging!(debug, Type::Window, false, "User idle since {}", idle_since);
+        self.insert_log(sink, idle_since, WindowEvent::IDLE, "")?;
+        self.cut_session(sink, idle_since)
+    }
+
+    /// Log a heartbeat if the last one is older than the heartbeat interval.
+    pub fn heartbeat(
+        &mut self,
+        sink: &dyn WriteSink,
+        now: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self
+            .last_heartbeat
+            .is_some_and(|t| now - t < ChronoDuration::seconds(HEARTBEAT_INTERVAL_SECS))
+        {
+            return Ok(());
+        }
+        self.last_heartbeat = Some(now);
+        // Not through `insert_log`, a heartbeat doesn't move where an idle marker goes
+        let log = LogRow {
+            time_ms: now.timestamp_millis(),
+            utc_offset: local_offset_secs(now),
+            app_name: WindowEvent::HEARTBEAT.to_string(),
+            title: String::new(),
+        };
+        sink.write(WriteOp::InsertLog(log))?;
+        self.extend_session(sink, now)
+    }
+
+    fn log_window(
+        &mut self,
+        sink: &dyn WriteSink,
+        time: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(window) = self.pre_window.clone() else {
+            return Ok(());
+        };
+        if W_IGNORE_APP_LIST.contains(&window.app_name.as_str()) {
+            return Ok(());
+        }
+        self.insert_log(sink, time, &window.app_name, &window.title)?;
+        self.open_session(sink, time, window, self.pre_source)
+    }
+
+    fn insert_log(
+        &mut self,
+        sink: &dyn WriteSink,
+        time: DateTime<Utc>,
+        app_name: &str,
+        title: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let log = LogRow {
+            time_ms: time.timestamp_millis(),
+            utc_offset: local_offset_secs(time),
+            app_name: app_name.to_string(),
+            title: title.to_string(),
+        };
+        sink.write(WriteOp::InsertLog(log))?;
+        self.last_logged = Some(time);
+        Ok(())
+    }
+
+    /// Close the open session and open a new one for `window`.
+    fn open_session(
+        &mut self,
+        sink: &dyn WriteSink,
+        time: DateTime<Utc>,
+        window: ForegroundWindow,
+        source: &'static str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.close_session(sink, time)?;
+        let session = SessionRow {
+            id: 0,
+            start_ms: time.timestamp_millis(),
+            end_ms: time.timestamp_millis(),
+            utc_offset: local_offset_secs(time),
+            app_name: window.app_name.clone(),
+            title: window.title.clone(),
+            source: source.to_string(),
+        };
+        sink.write(WriteOp::InsertSession(session))?;
+        self.session = Some(OpenSession {
+            start: time,
+            end: time,
+            window,
+            source,
+        });
+        Ok(())
+    }
+
+    /// End the open session at `time`, or at its last extension if the PC was
+    /// suspended in between.
+    fn close_session(
+        &mut self,
+        sink: &dyn WriteSink,
+        time: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(session) = self.session.take() else {
+            return Ok(());
+        };
+        // Without heartbeats a long gap can't be told from a long stay in one window
+        let suspended = self.last_heartbeat.is_some()
+            && time - session.end > ChronoDuration::seconds(SUSPEND_GAP_SECS);
+        if time > session.end && !suspended {
+            sink.write(WriteOp::UpdateLatestSessionEnd {
+                start_ms: session.start.timestamp_millis(),
+                end_ms: time.timestamp_millis(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// End the open session at `time`, even if heartbeats moved its end past it: they
+    /// go on while the user is away.
+    fn cut_session(
+        &mut self,
+        sink: &dyn WriteSink,
+        time: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(session) = self.session.take() else {
+            return Ok(());
+        };
+        if time >= session.end {
+            self.session = Some(session);
+            return self.close_session(sink, time);
+        }
+        sink.write(WriteOp::UpdateLatestSessionEnd {
+            start_ms: session.start.timestamp_millis(),
+            end_ms: time.timestamp_millis(),
+        })?;
+        Ok(())
+    }
+
+    /// Move the end of the open session to `time`.
+    fn extend_session(
+        &mut self,
+        sink: &dyn WriteSink,
+        time: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(mut session) = self.session.take() else {
+            return Ok(());
+        };
+        if time - session.end > ChronoDuration::seconds(SUSPEND_GAP_SECS) {
+            // The PC was suspended or off, the window is in use again from now on
+            return self.open_session(sink, time, session.window, session.source);
+        }
+        if time > session.end {
+            sink.write(WriteOp::UpdateLatestSessionEnd {
+                start_ms: session.start.timestamp_millis(),
+                end_ms: time.timestamp_millis(),
+            })?;
+            session.end = time;
+        }
+        self.session = Some(session);
+        Ok(())
+    }
+
+    /// Record events into `sink` until all senders are dropped.
+    pub fn run(&mut self, receiver: Receiver<ActivityEvent>, sink: &dyn WriteSink) {
+        loop {
+            let event = match receiver.recv_timeout(Duration::from_secs(1)) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            self.set_idle_threshold(ConfigManager::global().get().idle_threshold_secs);
+            let result = match event {
+                Some(event) => self.handle(sink, event),
+                None => Ok(()),
+            }
+            .and_then(|_| self.tick(sink, Utc::now()))
+            .and_then(|_| self.heartbeat(sink, Utc::now()));
+            if let Err(e) = result {
+                logging!(
+                    error,
+                    Type::Window,
+                    true,
+                    "Error inserting app usage log: {}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// The session of the logged window, still being extended.
+///
+/// Only the tracker opens sessions, so its session is the latest one, unless a backup
+/// was restored meanwhile. Its row is told by the start.
+struct OpenSession {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    window: ForegroundWindow,
+    source: &'static str,
+}
+
+/// End the latest session at `time`, used when the app exits while the tracker
+/// still has it open.
+///
+/// A session whose end is more than `SUSPEND_GAP_SECS` old was left behind by a
+/// suspend or an idle marker, and is not extended.
+pub fn close_open_session(
+    sink: &dyn WriteSink,
+    time: DateTime<Utc>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sink.write(WriteOp::ExtendLatestSession {
+        end_ms: time.timestamp_millis(),
+        max_gap_ms: SUSPEND_GAP_SECS * 1000,
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::core::activity::{ActivitySource, MockSource};
+    use crate::core::stats::{collect_app_usage_duration, collect_title_usage_duration};
+    use crate::utils::test::memory_db;
+    use chrono::{Local, NaiveDate, TimeZone};
+    use std::sync::mpsc;
+
+    fn at(date: NaiveDate, hms: (u32, u32, u32)) -> DateTime<Utc> {
+        let local = date.and_hms_opt(hms.0, hms.1, hms.2).unwrap();
+        Local
+            .from_local_datetime(&local)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn focus(date: NaiveDate, hms: (u32, u32, u32), app_name: &str, title: &str) -> ActivityEvent {
+        ActivityEvent::Focus {
+            time: at(date, hms),
+            app_name: app_name.into(),
+            title: title.into(),
+            source: "mock",
+        }
+    }
+
+    #[test]
+    pub fn test_mock_source_pipeline() {
+        let date = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let mut source = MockSource::new(vec![
+            focus(date, (9, 0, 0), "Visual Studio Code", "main.rs"),
+            // Repeated focus on the same window is not logged again
+            focus(date, (9, 10, 0), "Visual Studio Code", "main.rs"),
+            focus(date, (9, 30, 0), "Firefox", "Docs"),
+            // A new title inside the same app is logged
+            focus(date, (9, 35, 0), "Firefox", "Video"),
+            focus(date, (9, 40, 0), "Windows Shell Experience Host", ""),
+            focus(date, (9, 45, 0), "Visual Studio Code", "lib.rs"),
+            focus(date, (10, 0, 0), "Firefox", "Docs"),
+        ]);
+        let (sender, receiver) = mpsc::channel();
+        source.start(sender).unwrap();
+
+        let conn = memory_db();
+        let mut tracker = Tracker::new();
+        for event in receiver {
+            tracker.handle(&conn, event).unwrap();
+        }
+
+        let logged: i64 = conn
+            .query_row("SELECT COUNT(*) FROM app_usage_logs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(logged, 5);
+        let sessions: i64 = conn
+            .query_row("SELECT COUNT(*) FROM app_sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sessions, 5);
+
+        let usage = collect_app_usage_duration(&conn, date, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(30 * 60 + 15 * 60)));
+        // Ignored windows are not logged, so they are credited to the previous one
+        assert_eq!(usage.get("Firefox"), Some(&(15 * 60)));
+
+        let title_usage = collect_title_usage_duration(&conn, date, 0).unwrap();
+        let firefox = &title_usage["Firefox"];
+        assert_eq!(firefox.get("Docs"), Some(&(5 * 60)));
+        assert_eq!(firefox.get("Video"), Some(&(10 * 60)));
+        assert_eq!(
+            title_usage["Visual Studio Code"].get("lib.rs"),
+            Some(&(15 * 60))
+        );
+    }
+
+    #[test]
+    pub fn test_idle_detection() {
+        let date = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let conn = memory_db();
+        let mut tracker = Tracker::new();
+        tracker.set_idle_threshold(5 * 60);
+
+        let input = |h, m| ActivityEvent::Input {
+            time: at(date, (h, m, 0)),
+        };
+        // Input every few minutes from 9:00 to 9:14 and from 11:00 to 12:00
+        let mut events: Vec<ActivityEvent> = (0..=14).step_by(2).map(|m| input(9, m)).collect();
+        events.extend((0..60).step_by(2).map(|m| input(11, m)));
+        events.push(input(12, 0));
+        events.push(focus(date, (9, 0, 0), "Visual Studio Code", ""));
+        // Nobody is at the desk, the focus changes by itself
+        events.push(focus(date, (9, 20, 0), "Firefox", ""));
+        events.push(focus(date, (11, 30, 0), "Visual Studio Code", ""));
+        events.sort_by_key(|event| match event {
+            ActivityEvent::Focus { time, .. } | ActivityEvent::Input { time } => *time,
+        });
+
+        // Like `run`, every minute with heartbeats going on while the user is away
+        let mut events = events.into_iter().peekable();
+        let mut now = at(date, (9, 0, 0));
+        while now <= at(date, (12, 0, 0)) {
+            while let Some(event) = events.next_if(|event| match event {
+                ActivityEvent::Focus { time, .. } | ActivityEvent::Input { time } => *time <= now,
+            }) {
+                tracker.handle(&conn, event).unwrap();
+            }
+            tracker.tick(&conn, now).unwrap();
+            tracker.heartbeat(&conn, now).unwrap();
+            now += ChronoDuration::minutes(1);
+        }
+
+        let usage = collect_app_usage_duration(&conn, date, 0).unwrap();
+        // Idle from the last input at 9:14 until 11:00, the heartbeats in between aren't
+        // credited
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(14 * 60 + 30 * 60)));
+        assert_eq!(usage.get("Firefox"), Some(&(30 * 60)));
+        assert_eq!(usage.get(WindowEvent::IDLE), None);
+    }
+
+    #[test]
+    pub fn test_sessions_skip_suspend() {
+        let date = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let conn = memory_db();
+        let mut tracker = Tracker::new();
+
+        tracker
+            .handle(&conn, focus(date, (9, 0, 0), "Visual Studio Code", ""))
+            .unwrap();
+        for minute in 0..=10 {
+            tracker.heartbeat(&conn, at(date, (9, minute, 0))).unwrap();
+        }
+        // Suspended from 9:10 to 10:00, the window is still focused after resume
+        for minute in 0..=5 {
+            tracker.heartbeat(&conn, at(date, (10, minute, 0))).unwrap();
+        }
+        tracker
+            .handle(&conn, focus(date, (10, 5, 0), "Firefox", ""))
+            .unwrap();
+        tracker.heartbeat(&conn, at(date, (10, 6, 0))).unwrap();
+        close_open_session(&conn, at(date, (10, 7, 0))).unwrap();
+
+        let mut stmt = conn
+            .prepare("SELECT app_name, source FROM app_sessions ORDER BY id")
+            .unwrap();
+        let sessions: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sessions.len(), 3);
+        assert!(sessions.iter().all(|(_, source)| source == "mock"));
+
+        let usage = collect_app_usage_duration(&conn, date, 0).unwrap();
+        assert_eq!(usage.get("Visual Studio Code"), Some(&(15 * 60)));
+        assert_eq!(usage.get("Firefox"), Some(&(2 * 60)));
+    }
+}
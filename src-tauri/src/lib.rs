@@ -1,6 +1,5 @@
 use chrono::Utc;
-use rdev::{Event, EventType};
-use rusqlite::params;
+use std::sync::mpsc;
 use std::thread;
 use tauri::{
     menu::{Menu, MenuItem},
@@ -10,27 +9,42 @@ use tauri::{
 mod constants;
 mod core;
 mod utils;
-use constants::db::TABLE;
-use constants::window::{W_IGNORE_APP_LIST, WindowEvent};
+use constants::db::BACKUP_INTERVAL_SECS;
+use constants::window::WindowEvent;
+use core::activity::{ActivitySource, InputHookSource, PollingSource, default_source};
+use core::category::{
+    create_category, delete_category, get_app_categories, get_categories,
+    get_category_usage_duration_last_n_days, get_category_usage_duration_range, rename_category,
+    set_app_category,
+};
+use core::compare::compare_periods;
 use core::report::export_report;
+use core::retention::compact_logs;
 use core::stats::{
-    get_app_usage_duration_last_n_days, get_app_usage_duration_range,
-    get_daily_usage_duration_last_n_days, refresh_data, update_daily_app_usage,
+    finalize_past_days, get_app_ranking, get_app_usage_duration_last_n_days,
+    get_app_usage_duration_range, get_daily_usage_duration_last_n_days, get_day_timeline,
+    get_focus_stats_range, get_hourly_heatmap, get_title_usage_duration_last_n_days,
+    get_title_usage_duration_range, recompute_range, refresh_data, update_daily_app_usage,
     update_daily_usage_stats,
 };
-use core::task::register_event_listener;
 use core::task::register_scheduled_task;
+use core::tracker::{Tracker, close_open_session};
 use parking_lot::Mutex;
 use tauri::AppHandle;
 use utils::autostart::set_start_on_boot_rs;
-use utils::db::{DbManager, init_db, insert};
+use utils::backup::{backup_database, restore_backup};
+use utils::config::{ConfigManager, get_config, set_config};
+use utils::db::{init_db, open_db};
 use utils::logging;
 use utils::logging::Type;
+use utils::repo::LogRow;
+use utils::time::{local_date, local_offset_secs};
 use utils::window::WindowManager;
 use utils::window::{
-    current_window, window_close, window_minimize, window_start_drag, window_toggle_always_on_top,
+    window_close, window_minimize, window_start_drag, window_toggle_always_on_top,
     window_toggle_maximize,
 };
+use utils::writer::{DbWriter, WriteOp, WriteSink};
 
 pub struct AppHandleManager {
     handle: Mutex<Option<AppHandle>>,
@@ -92,12 +106,32 @@ mod app_init {
     -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool + Send + Sync + 'static {
         tauri::generate_handler![
             // Dashboard statistics
+            compare_periods,
+            get_app_ranking,
             get_app_usage_duration_last_n_days,
             get_app_usage_duration_range,
             get_daily_usage_duration_last_n_days,
+            get_day_timeline,
+            get_focus_stats_range,
+            get_hourly_heatmap,
+            get_title_usage_duration_last_n_days,
+            get_title_usage_duration_range,
             refresh_data,
+            recompute_range,
+            get_category_usage_duration_last_n_days,
+            get_category_usage_duration_range,
+            // Categories
+            get_categories,
+            create_category,
+            rename_category,
+            delete_category,
+            get_app_categories,
+            set_app_category,
             // Settings
             set_start_on_boot_rs,
+            get_config,
+            set_config,
+            restore_backup,
             // Export
             export_report,
             // Window event
@@ -174,6 +208,17 @@ pub fn run() {
     if let Err(e) = logging::init_logger() {
         eprintln!("Failed to initialize logger: {}", e);
     }
+    // Migrate the database before anything reads or writes it
+    if let Err(e) = init_db() {
+        logging!(
+            error,
+            Type::Database,
+            true,
+            "Failed to open database: {}",
+            e
+        );
+        std::process::exit(1);
+    }
 
     // The thread where scheduled tasks and event listening run
     thread::spawn(|| {
@@ -181,39 +226,127 @@ pub fn run() {
         register_scheduled_task(
             "update_daily_app_usage",
             || {
-                let conn = init_db().expect("Error initializing database");
-                update_daily_app_usage(&conn).expect("Error updating daily app usage");
+                let day_start_hour = ConfigManager::global().get().day_start_hour;
+                let result = open_db().and_then(|conn| {
+                    update_daily_app_usage(&conn, DbWriter::global(), day_start_hour)
+                });
+                if let Err(e) = result {
+                    logging!(
+                        error,
+                        Type::Statistics,
+                        false,
+                        "Failed to update daily app usage: {}",
+                        e
+                    );
+                }
             },
             Duration::from_secs(120),
         );
         register_scheduled_task(
             "update_daily_usage_stats",
             || {
-                let conn = init_db().expect("Error initializing database");
-                update_daily_usage_stats(&conn).expect("Error updating daily usage stats");
+                let day_start_hour = ConfigManager::global().get().day_start_hour;
+                let result = open_db().and_then(|conn| {
+                    update_daily_usage_stats(&conn, DbWriter::global(), day_start_hour)
+                });
+                if let Err(e) = result {
+                    logging!(
+                        error,
+                        Type::Statistics,
+                        false,
+                        "Failed to update daily usage stats: {}",
+                        e
+                    );
+                }
             },
             Duration::from_secs(600),
         );
-        let mut pre_cw: String = "".to_string();
-        register_event_listener("move_click", move |evt: Event| match evt.event_type {
-            EventType::ButtonRelease(_) | EventType::KeyRelease(_) => {
-                let cw = current_window();
-                if pre_cw == cw {
-                    return;
-                };
-                logging!(debug, Type::Window, false, "Current window: {}", cw);
-                pre_cw = cw.clone();
-                if W_IGNORE_APP_LIST.contains(&cw.as_str()) {
-                    return;
+        // Also runs at startup, to catch up on the days the app wasn't running at the end of
+        register_scheduled_task(
+            "finalize_past_days",
+            || {
+                let day_start_hour = ConfigManager::global().get().day_start_hour;
+                let result = open_db()
+                    .and_then(|conn| finalize_past_days(&conn, DbWriter::global(), day_start_hour));
+                if let Err(e) = result {
+                    logging!(
+                        error,
+                        Type::Statistics,
+                        true,
+                        "Failed to finalize past days: {}",
+                        e
+                    );
                 }
-                let time_stamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-                let params = params![&time_stamp, &cw];
-                let conn = DbManager::global().get().lock();
-                insert(&conn, TABLE::APP_USAGE_LOGS, params)
-                    .expect("Error inserting app usage log");
-            }
-            _ => {}
-        });
+            },
+            Duration::from_secs(3600),
+        );
+        register_scheduled_task(
+            "compact_logs",
+            || {
+                let config = ConfigManager::global().get();
+                let today = local_date(config.day_start_hour);
+                let result = open_db().and_then(|conn| {
+                    compact_logs(
+                        &conn,
+                        DbWriter::global(),
+                        today,
+                        config.log_retention_days,
+                        config.day_start_hour,
+                    )
+                });
+                if let Err(e) = result {
+                    logging!(error, Type::Database, true, "Failed to compact logs: {}", e);
+                }
+            },
+            Duration::from_secs(24 * 3600),
+        );
+        register_scheduled_task(
+            "backup_database",
+            || {
+                let backup_count = ConfigManager::global().get().backup_count;
+                let result = open_db().and_then(|conn| backup_database(&conn, backup_count));
+                if let Err(e) = result {
+                    logging!(
+                        error,
+                        Type::Database,
+                        true,
+                        "Failed to back up database: {}",
+                        e
+                    );
+                }
+            },
+            Duration::from_secs(BACKUP_INTERVAL_SECS),
+        );
+        let (sender, receiver) = mpsc::channel();
+        let mut source = default_source();
+        logging!(
+            info,
+            Type::Setup,
+            false,
+            "Activity source: {}",
+            source.name()
+        );
+        if let Err(e) = source.start(sender.clone()) {
+            // e.g. the compositor socket went away, input hooks work everywhere
+            logging!(
+                warn,
+                Type::Setup,
+                true,
+                "Failed to start activity source {}: {}",
+                source.name(),
+                e
+            );
+            source.stop();
+            source = Box::new(InputHookSource::new());
+            source
+                .start(sender.clone())
+                .expect("Error starting activity source");
+        }
+        // Focus changes without any input are only seen by polling
+        PollingSource::new()
+            .start(sender)
+            .expect("Error starting polling source");
+        Tracker::new().run(receiver, DbWriter::global());
     });
 
     let builder = app_init::setup_plugins(
@@ -235,18 +368,30 @@ pub fn run() {
         match _event {
             tauri::RunEvent::ExitRequested { api: _, .. } => {
                 // api.prevent_exit();
-                let time_stamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-                let params = params![&time_stamp, WindowEvent::EXITED];
-                let conn = DbManager::global().get().lock();
-                if let Err(e) = insert(&conn, TABLE::APP_USAGE_LOGS, params) {
+                let now = Utc::now();
+                let log = LogRow {
+                    time_ms: now.timestamp_millis(),
+                    utc_offset: local_offset_secs(now),
+                    app_name: WindowEvent::EXITED.to_string(),
+                    title: String::new(),
+                };
+                let writer = DbWriter::global();
+                if let Err(e) = writer.write(WriteOp::InsertLog(log)) {
                     eprintln!("Error inserting close log: {}", e);
                 }
+                if let Err(e) = close_open_session(writer, now) {
+                    eprintln!("Error closing app session: {}", e);
+                }
+                // The process is about to end, don't leave writes in the buffer
+                if let Err(e) = writer.flush() {
+                    eprintln!("Error flushing database writes: {}", e);
+                }
                 logging!(
                     info,
                     Type::Exit,
                     false,
                     "App usage log inserted: [{} - {}]",
-                    time_stamp,
+                    now,
                     WindowEvent::EXITED
                 );
             }
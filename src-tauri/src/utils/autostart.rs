@@ -2,7 +2,6 @@ use crate::AppHandleManager;
 use crate::{logging, utils::logging::Type};
 use tauri_plugin_autostart::ManagerExt;
 
-#[cfg(target_os = "windows")]
 pub fn set_start_on_boot(enable: bool) -> Result<(), Box<dyn std::error::Error>> {
     let app = AppHandleManager::global().get().unwrap();
     let app_launcher = app.autolaunch();
@@ -0,0 +1,323 @@
+use super::db::{open_connection, open_db};
+use super::migration::{MigrationError, latest_version, migrate, user_version};
+use super::paths::data_dir;
+use super::writer::{DbWriter, WriteSink};
+use crate::constants::db::{BACKUP_DIR_NAME, BACKUP_INTERVAL_SECS, BACKUP_PREFIX};
+use crate::logging;
+use crate::utils::logging::Type;
+use chrono::{DateTime, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+fn backup_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(data_dir()?.join(BACKUP_DIR_NAME))
+}
+
+/// Back the database up to the backup dir, keeping the `keep` latest backups. Does
+/// nothing if `keep` is 0 or the latest backup is less than a backup interval old, so
+/// restarting the app doesn't rotate the older backups out.
+pub fn backup_database(
+    conn: &Connection,
+    keep: usize,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let dir = backup_dir()?;
+    if keep == 0 || backed_up_within(&dir, Duration::from_secs(BACKUP_INTERVAL_SECS))? {
+        return Ok(None);
+    }
+    let path = backup_to(conn, &dir, Utc::now(), keep)?;
+    logging!(
+        info,
+        Type::Database,
+        false,
+        "Database backed up to {}",
+        path.display()
+    );
+    Ok(Some(path))
+}
+
+/// Copy the database of `conn` to a new file of `dir` named after `time`, check the
+/// copy, then delete all but the `keep` latest backups of `dir`. Returns the path of
+/// the copy.
+///
+/// Uses SQLite's online backup, the writer keeps going meanwhile. A copy which fails
+/// the check is deleted, so a corrupted database never rotates the good backups out.
+pub fn backup_to(
+    conn: &Connection,
+    dir: &Path,
+    time: DateTime<Utc>,
+    keep: usize,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let path = new_backup_path(dir, time)?;
+    let copy = || -> Result<(), Box<dyn std::error::Error>> {
+        // Keyed like the database, SQLCipher can't back up into a plaintext file
+        let mut dst = open_connection(&path, OpenFlags::default())?;
+        copy_database(conn, &mut dst)?;
+        // A single file, reading it doesn't leave WAL files behind
+        dst.pragma_update(None, "journal_mode", "DELETE")?;
+        drop(dst);
+        validate_backup(&path)?;
+        Ok(())
+    };
+    if let Err(e) = copy() {
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+    rotate(dir, keep)?;
+    Ok(path)
+}
+
+/// Create an empty file of `dir` for a backup taken at `time`. The names sort by time,
+/// a number is appended to tell apart backups of the same millisecond.
+fn new_backup_path(dir: &Path, time: DateTime<Utc>) -> std::io::Result<PathBuf> {
+    let stem = format!("{}{}", BACKUP_PREFIX, time.format("%Y%m%d_%H%M%S_%3fZ"));
+    for n in 0.. {
+        let path = match n {
+            0 => dir.join(format!("{}.db", stem)),
+            _ => dir.join(format!("{}_{}.db", stem, n)),
+        };
+        // Only succeeds if there's no such file yet, an existing backup is never reused
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// Whether a backup of `dir` was written less than `within` ago
+fn backed_up_within(dir: &Path, within: Duration) -> std::io::Result<bool> {
+    let latest = match backups(dir) {
+        Ok(backups) => backups
+            .iter()
+            .filter_map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+            .max(),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    Ok(latest.is_some_and(|time| {
+        SystemTime::now()
+            .duration_since(time)
+            .is_ok_and(|age| age < within)
+    }))
+}
+
+/// Copy all pages in one step. Copying a few pages at a time would restart whenever
+/// another connection writes to `src`.
+fn copy_database(src: &Connection, dst: &mut Connection) -> Result<(), rusqlite::Error> {
+    Backup::new(src, dst)?.run_to_completion(i32::MAX, Duration::from_millis(100), None)
+}
+
+/// The backups of `dir`
+fn backups(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(BACKUP_PREFIX) && name.ends_with(".db"))
+        })
+        .collect())
+}
+
+/// Delete all but the `keep` latest backups of `dir`
+fn rotate(dir: &Path, keep: usize) -> std::io::Result<()> {
+    let mut backups = backups(dir)?;
+    // The names sort by time
+    backups.sort();
+    let outdated = backups.len().saturating_sub(keep);
+    for path in &backups[..outdated] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Check that `path` is a sound database of this app, which it can migrate. Returns its
+/// schema version.
+pub fn validate_backup(path: &Path) -> Result<u32, Box<dyn std::error::Error>> {
+    let conn = open_connection(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let check: String = conn.pragma_query_value(None, "quick_check", |row| row.get(0))?;
+    if check != "ok" {
+        return Err(format!("Backup is corrupted: {}", check).into());
+    }
+    let version = user_version(&conn)?;
+    if version == 0 {
+        return Err("Backup has no schema version, it isn't a database of this app".into());
+    }
+    if version > latest_version() {
+        return Err(MigrationError::TooNew {
+            version,
+            latest: latest_version(),
+        }
+        .into());
+    }
+    Ok(version)
+}
+
+/// Replace the database of `conn` by the backup at `path`, then migrate it. Other
+/// connections see the restored data on their next read.
+pub fn restore_from(conn: &mut Connection, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    validate_backup(path)?;
+    let src = open_connection(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    copy_database(&src, conn)?;
+    migrate(conn)?;
+    Ok(())
+}
+
+/// Restore the database from the backup at `path`. The current database is backed up
+/// first, so the restore can be undone.
+#[tauri::command]
+pub fn restore_backup(path: String) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    let restore = || -> Result<PathBuf, Box<dyn std::error::Error>> {
+        validate_backup(&path)?;
+        // Commit the pending writes into the safety backup rather than over the restore
+        DbWriter::global().flush()?;
+        let mut conn = open_db()?;
+        let current = backup_to(&conn, &backup_dir()?, Utc::now(), usize::MAX)?;
+        restore_from(&mut conn, &path)?;
+        Ok(current)
+    };
+    match restore() {
+        Ok(current) => {
+            logging!(
+                info,
+                Type::Database,
+                true,
+                "Database restored from {}, the previous one was backed up to {}",
+                path.display(),
+                current.display()
+            );
+            Ok(())
+        }
+        Err(e) => {
+            logging!(
+                error,
+                Type::Database,
+                true,
+                "Failed to restore database from {}: {}",
+                path.display(),
+                e
+            );
+            Err(format!("Error occured: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::core::activity::ActivityEvent;
+    use crate::core::tracker::Tracker;
+    use crate::utils::db::{init_db_at, open_db_at};
+    use crate::utils::repo::{self, LogRow};
+    use chrono::TimeZone;
+
+    fn log(conn: &Connection, time_ms: i64) {
+        let log = LogRow {
+            time_ms,
+            utc_offset: 0,
+            app_name: "Firefox".into(),
+            title: String::new(),
+        };
+        repo::insert_log(conn, &log).unwrap();
+    }
+
+    #[test]
+    pub fn test_backup_and_restore() {
+        let root = std::env::temp_dir().join(format!("pc_monitor_backup_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let dir = root.join("backups");
+        let mut conn = init_db_at(&root.join("live.db")).unwrap();
+        log(&conn, 1000);
+
+        assert!(!backed_up_within(&dir, Duration::from_secs(3600)).unwrap());
+        let first = backup_to(&conn, &dir, Utc.timestamp_opt(1000, 0).unwrap(), 2).unwrap();
+        assert!(backed_up_within(&dir, Duration::from_secs(3600)).unwrap());
+        log(&conn, 2000);
+        // Taken in the same second, neither overwrites the other
+        let second = backup_to(&conn, &dir, Utc.timestamp_opt(2000, 0).unwrap(), 2).unwrap();
+        let last = backup_to(&conn, &dir, Utc.timestamp_opt(2000, 0).unwrap(), 2).unwrap();
+        assert_ne!(second, last);
+        // Only the 2 latest are kept
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert!(!first.exists());
+        assert_eq!(validate_backup(&last).unwrap(), latest_version());
+
+        // A copy failing the check is deleted and doesn't rotate the others out
+        let other_db = Connection::open_in_memory().unwrap();
+        assert!(backup_to(&other_db, &dir, Utc.timestamp_opt(4000, 0).unwrap(), 1).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Not a database of the app
+        let other = root.join("other.db");
+        Connection::open(&other)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x INTEGER);")
+            .unwrap();
+        assert!(validate_backup(&other).is_err());
+        let garbage = root.join("garbage.db");
+        fs::write(&garbage, "not a database").unwrap();
+        assert!(validate_backup(&garbage).is_err());
+        assert!(restore_from(&mut conn, &garbage).is_err());
+        assert_eq!(repo::count_logs(&conn).unwrap(), 2);
+
+        log(&conn, 3000);
+        let reader = open_db_at(&root.join("live.db")).unwrap();
+        restore_from(&mut conn, &last).unwrap();
+        assert_eq!(repo::count_logs(&conn).unwrap(), 2);
+        assert_eq!(repo::count_logs(&reader).unwrap(), 2);
+
+        drop((conn, reader));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    pub fn test_restore_with_open_session() {
+        let root = std::env::temp_dir().join(format!("pc_monitor_restore_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let mut conn = init_db_at(&root.join("live.db")).unwrap();
+        let time = |hour, minute| Utc.with_ymd_and_hms(2025, 9, 1, hour, minute, 0).unwrap();
+        let focus = |hour, app_name: &str| ActivityEvent::Focus {
+            time: time(hour, 0),
+            app_name: app_name.into(),
+            title: String::new(),
+            source: "mock",
+        };
+
+        let mut tracker = Tracker::new();
+        tracker.handle(&conn, focus(9, "Steam")).unwrap();
+        tracker.heartbeat(&conn, time(9, 1)).unwrap();
+        let backup = backup_to(
+            &conn,
+            &root.join("backups"),
+            Utc.timestamp_opt(1000, 0).unwrap(),
+            1,
+        )
+        .unwrap();
+        tracker.handle(&conn, focus(10, "Firefox")).unwrap();
+        tracker.heartbeat(&conn, time(10, 1)).unwrap();
+
+        // The tracker's session isn't in the backup, the latest one there is left as it is
+        restore_from(&mut conn, &backup).unwrap();
+        tracker.heartbeat(&conn, time(10, 2)).unwrap();
+        let latest = repo::latest_session(&conn).unwrap().unwrap();
+        assert_eq!(latest.app_name, "Steam");
+        assert_eq!(latest.end_ms, time(9, 1).timestamp_millis());
+        assert_eq!(repo::count_sessions(&conn).unwrap(), 1);
+
+        drop(conn);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use super::migration::user_version;
+use super::paths::config_dir;
+use crate::constants::cipher::{DB_KEY_ENV, DB_KEY_FILE_ENV, DB_KEY_FILE_NAME};
+use crate::logging;
+use crate::utils::logging::Type;
+use rusqlite::{Connection, DatabaseName, params};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::{env, fmt};
+
+static KEY: OnceLock<DatabaseKey> = OnceLock::new();
+
+/// The passphrase of the database, which SQLCipher derives the key from.
+#[derive(Clone)]
+pub struct DatabaseKey(String);
+
+/// Don't let the passphrase end up in logs
+impl fmt::Debug for DatabaseKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DatabaseKey(..)")
+    }
+}
+
+impl DatabaseKey {
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Self(passphrase.into())
+    }
+}
+
+/// Get the key of the database, read once per launch from `PC_MONITOR_DB_KEY`, or else
+/// from the key file at `PC_MONITOR_DB_KEY_FILE`, or else `db.key` in the config dir.
+pub fn database_key() -> Result<DatabaseKey, Box<dyn std::error::Error>> {
+    if let Some(key) = KEY.get() {
+        return Ok(key.clone());
+    }
+    let key = match env::var(DB_KEY_ENV) {
+        Ok(passphrase) if !passphrase.is_empty() => DatabaseKey::new(passphrase),
+        _ => {
+            let path = match env::var_os(DB_KEY_FILE_ENV) {
+                Some(path) if !path.is_empty() => PathBuf::from(path),
+                _ => config_dir()?.join(DB_KEY_FILE_NAME),
+            };
+            read_key_file(&path)?
+        }
+    };
+    Ok(KEY.get_or_init(|| key).clone())
+}
+
+/// The whole file is the passphrase, except a trailing line break
+fn read_key_file(path: &Path) -> Result<DatabaseKey, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        format!(
+            "No database key, set {} or write one to {}: {}",
+            DB_KEY_ENV,
+            path.display(),
+            e
+        )
+    })?;
+    let passphrase = content.trim_end_matches(['\r', '\n']);
+    if passphrase.is_empty() {
+        return Err(format!("Database key file {} is empty", path.display()).into());
+    }
+    Ok(DatabaseKey::new(passphrase))
+}
+
+/// Key `conn`, before anything else is done with it, and check that the key opens it.
+pub fn apply_key(conn: &Connection, key: &DatabaseKey) -> Result<(), Box<dyn std::error::Error>> {
+    conn.pragma_update(None, "key", &key.0)?;
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|e| format!("Wrong database key or not an encrypted database: {}", e))?;
+    Ok(())
+}
+
+/// Whether `path` is a plaintext SQLite database, encrypted ones have no readable header.
+fn is_plaintext(path: &Path) -> Result<bool, std::io::Error> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let mut header = [0; 16];
+    match File::open(path)?.read_exact(&mut header) {
+        Ok(()) => Ok(&header == b"SQLite format 3\0"),
+        // Too short for a header, nothing was written yet
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Encrypt the database at `path` in place with `key`, if it's a plaintext one. Returns
+/// whether it was converted. No other connection may be open on it.
+pub fn encrypt_plaintext(
+    path: &Path,
+    key: &DatabaseKey,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if !is_plaintext(path)? {
+        return Ok(false);
+    }
+    let encrypted = PathBuf::from(format!("{}.encrypting", path.display()));
+    let _ = fs::remove_file(&encrypted);
+    {
+        let conn = Connection::open(path)?;
+        // Move all writes into the file and empty the WAL, the export must not miss any
+        let busy: i64 = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+        if busy != 0 {
+            return Err("Failed to checkpoint the database before encrypting it".into());
+        }
+        let version = user_version(&conn)?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted.to_string_lossy(), key.0],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+        conn.pragma_update(
+            Some(DatabaseName::Attached("encrypted")),
+            "user_version",
+            version,
+        )?;
+        conn.execute_batch("DETACH DATABASE encrypted")?;
+    }
+    fs::rename(&encrypted, path)?;
+    // Emptied by the checkpoint, so there's nothing to lose if they stay behind
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+    logging!(
+        info,
+        Type::Database,
+        true,
+        "Encrypted the plaintext database {}",
+        path.display()
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::utils::migration::{latest_version, migrate};
+    use crate::utils::repo::{self, LogRow};
+    use crate::utils::test::logs_between;
+
+    #[test]
+    pub fn test_encrypt_plaintext() {
+        let root = env::temp_dir().join(format!("pc_monitor_cipher_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("plain.db");
+        let key = DatabaseKey::new("correct horse battery staple");
+
+        // A database of a build without the feature
+        let mut conn = Connection::open(&path).unwrap();
+        migrate(&mut conn).unwrap();
+        let log = LogRow {
+            time_ms: 1000,
+            utc_offset: 0,
+            app_name: "Firefox".into(),
+            title: "secret title".into(),
+        };
+        repo::insert_log(&conn, &log).unwrap();
+        drop(conn);
+
+        assert!(encrypt_plaintext(&path, &key).unwrap());
+        assert!(!is_plaintext(&path).unwrap());
+        assert!(
+            !fs::read(&path)
+                .unwrap()
+                .windows(12)
+                .any(|w| w == b"secret title")
+        );
+        // Already encrypted
+        assert!(!encrypt_plaintext(&path, &key).unwrap());
+
+        let conn = Connection::open(&path).unwrap();
+        assert!(apply_key(&conn, &DatabaseKey::new("wrong")).is_err());
+        let conn = Connection::open(&path).unwrap();
+        apply_key(&conn, &key).unwrap();
+        assert_eq!(logs_between(&conn, 0, 2000).unwrap(), vec![log]);
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        drop(conn);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
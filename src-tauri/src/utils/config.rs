@@ -0,0 +1,197 @@
+use super::paths::{config_dir, migrate_legacy_files};
+use crate::constants::config::{
+    CONFIG_NAME, DEFAULT_BACKUP_COUNT, DEFAULT_DAY_START_HOUR, DEFAULT_IDLE_THRESHOLD_SECS,
+    DEFAULT_LOG_RETENTION_DAYS, DEFAULT_POLL_INTERVAL_SECS,
+};
+use crate::core::stats::recompute_history;
+use crate::logging;
+use crate::singleton_with_logging;
+use crate::utils::db::open_db;
+use crate::utils::logging::Type;
+use crate::utils::time::check_day_start_hour;
+use crate::utils::writer::{DbWriter, WriteOp, WriteSink};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+/// Counts the changes of the start of the day, a recompute is skipped once a newer
+/// change is waiting
+static RECOMPUTE_GENERATION: AtomicU64 = AtomicU64::new(0);
+/// Held while the history is recomputed, so recomputes don't overlap
+static RECOMPUTE_LOCK: Mutex<()> = Mutex::new(());
+
+/// User settings of the tracker, persisted as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Seconds without input before an idle marker is logged, 0 disables idle detection
+    pub idle_threshold_secs: u64,
+    /// Seconds between two samples of the foreground window, 0 disables polling
+    pub poll_interval_secs: u64,
+    /// Where the database and logs are kept, the platform data dir if unset. Applies
+    /// on the next launch, existing data isn't moved.
+    pub data_dir: Option<PathBuf>,
+    /// Hour at which a day starts in the statistics, so late nights count for the day
+    /// before. Changing it recomputes the whole history.
+    pub day_start_hour: u32,
+    /// Days of raw app usage logs kept, 0 keeps them forever. Sessions and aggregates
+    /// are always kept.
+    pub log_retention_days: u64,
+    /// Daily backups of the database kept, 0 disables backups
+    pub backup_count: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            idle_threshold_secs: DEFAULT_IDLE_THRESHOLD_SECS,
+            poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            data_dir: None,
+            day_start_hour: DEFAULT_DAY_START_HOUR,
+            log_retention_days: DEFAULT_LOG_RETENTION_DAYS,
+            backup_count: DEFAULT_BACKUP_COUNT,
+        }
+    }
+}
+
+pub struct ConfigManager {
+    config: Mutex<Config>,
+}
+
+impl ConfigManager {
+    pub fn new() -> Self {
+        let config = match load_config() {
+            Ok(config) => config,
+            Err(e) => {
+                logging!(
+                    warn,
+                    Type::Setup,
+                    true,
+                    "Failed to load config, using defaults: {}",
+                    e
+                );
+                Config::default()
+            }
+        };
+        Self {
+            config: Mutex::new(config),
+        }
+    }
+
+    /// Get a copy of the current config
+    pub fn get(&self) -> Config {
+        self.config.lock().clone()
+    }
+
+    /// Replace the config and persist it
+    pub fn set(&self, config: Config) -> Result<(), Box<dyn std::error::Error>> {
+        save_config(&config)?;
+        *self.config.lock() = config;
+        Ok(())
+    }
+}
+
+singleton_with_logging!(ConfigManager, CONFIG);
+
+fn config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(config_dir()?.join(CONFIG_NAME))
+}
+
+fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+    migrate_legacy_files(&[CONFIG_NAME], &config_dir()?);
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    let mut config: Config = serde_json::from_str(&content)?;
+    // The file may have been edited by hand
+    if let Err(e) = check_day_start_hour(config.day_start_hour) {
+        logging!(
+            warn,
+            Type::Setup,
+            true,
+            "{} in config, using {}",
+            e,
+            DEFAULT_DAY_START_HOUR
+        );
+        config.day_start_hour = DEFAULT_DAY_START_HOUR;
+    }
+    Ok(config)
+}
+
+fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let content = serde_json::to_string_pretty(config)?;
+    fs::create_dir_all(config_dir()?)?;
+    fs::write(config_path()?, content)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_config() -> Config {
+    ConfigManager::global().get()
+}
+
+#[tauri::command]
+pub fn set_config(config: Config) -> Result<(), String> {
+    check_day_start_hour(config.day_start_hour).map_err(|e| e.to_string())?;
+    logging!(info, Type::Setup, false, "Config updated: {:?}", config);
+    let previous = ConfigManager::global().get();
+    let day_start_hour = config.day_start_hour;
+    ConfigManager::global()
+        .set(config)
+        .map_err(|e| e.to_string())?;
+    if previous.day_start_hour != day_start_hour {
+        spawn_recompute_history(day_start_hour);
+    }
+    Ok(())
+}
+
+/// Recompute the history for `day_start_hour` in the background, the days of all the
+/// history moved so it takes a while. Recomputes run one at a time, and only the one
+/// for the latest hour runs.
+fn spawn_recompute_history(day_start_hour: u32) {
+    let generation = RECOMPUTE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    thread::spawn(move || {
+        let _guard = RECOMPUTE_LOCK.lock();
+        if RECOMPUTE_GENERATION.load(Ordering::SeqCst) != generation {
+            logging!(
+                debug,
+                Type::Statistics,
+                false,
+                "Skipped recomputing history for day start hour {}, it changed again",
+                day_start_hour
+            );
+            return;
+        }
+        let result =
+            open_db().and_then(|conn| recompute_history(&conn, DbWriter::global(), day_start_hour));
+        let Err(e) = result else {
+            return;
+        };
+        logging!(
+            error,
+            Type::Statistics,
+            true,
+            "Failed to recompute history: {}",
+            e
+        );
+        // Some days may be left finalized for the previous hour, have them all recomputed
+        let writer = DbWriter::global();
+        if let Err(e) = writer
+            .write(WriteOp::ClearFinalizedDays)
+            .and_then(|_| writer.flush())
+        {
+            logging!(
+                error,
+                Type::Statistics,
+                true,
+                "Failed to clear the finalized days: {}",
+                e
+            );
+        }
+    });
+}
@@ -1,106 +1,115 @@
-use super::file::get_exe_path;
+#[cfg(feature = "encryption")]
+use super::cipher;
+use super::migration::migrate;
+use super::paths::{data_dir, migrate_legacy_files};
 use crate::{
-    constants::db::{DB_NAME, TABLE},
+    constants::db::{DB_BUSY_TIMEOUT_MS, DB_NAME, DB_READER_POOL_SIZE},
     singleton_with_logging,
 };
 use parking_lot::Mutex;
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+/// The connections the Tauri commands read from, each command gets its own so none
+/// waits on another. Writes go through the [`DbWriter`](super::writer::DbWriter).
 pub struct DbManager {
-    pub handle: Mutex<Connection>,
+    idle: Mutex<Vec<Connection>>,
 }
 
 impl DbManager {
     pub fn new() -> Self {
         Self {
-            handle: Mutex::new(init_db().expect("Failed to initialize database")),
+            idle: Mutex::new(vec![init_db().expect("Failed to initialize database")]),
         }
     }
 
-    pub fn get(&self) -> &Mutex<Connection> {
-        &self.handle
+    /// Take an idle connection, or open a new one. It's given back once dropped.
+    pub fn get(&self) -> Result<PooledConnection<'_>, Box<dyn std::error::Error>> {
+        let idle = self.idle.lock().pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => open_db()?,
+        };
+        Ok(PooledConnection {
+            conn: Some(conn),
+            pool: self,
+        })
     }
 }
 
-singleton_with_logging!(DbManager, DB_CONN);
+/// A connection taken from the [`DbManager`]
+pub struct PooledConnection<'a> {
+    conn: Option<Connection>,
+    pool: &'a DbManager,
+}
 
-/// Initialize the database and create necessary tables.
-pub fn init_db() -> Result<Connection, Box<dyn std::error::Error>> {
-    let exe_path = get_exe_path()?;
-    let db_path = exe_path
-        .parent()
-        .ok_or("Failed to get exe parent dir")?
-        .join(DB_NAME);
-    let conn = Connection::open(&db_path)?;
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
 
-    let tables = [
-        (
-            TABLE::APP_USAGE_LOGS,
-            "time DATETIME NOT NULL, app_name TEXT NOT NULL",
-        ),
-        (
-            TABLE::DAILY_APP_USAGE,
-            "date DATETIME NOT NULL, app_name TEXT NOT NULL, total_usage INTEGER NOT NULL, UNIQUE (date, app_name)",
-        ),
-        (
-            TABLE::DAILY_USAGE_STATS,
-            "date DATETIME NOT NULL, total_usage INTEGER NOT NULL, UNIQUE (date)",
-        ),
-    ];
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("Connection given back")
+    }
+}
 
-    for (table_name, columns) in tables {
-        let _ = conn
-            .execute(
-                &format!(
-                    "CREATE TABLE IF NOT EXISTS {} (
-                    id INTEGER PRIMARY KEY,
-                    {}
-                )",
-                    table_name, columns
-                ),
-                [],
-            )
-            .map_err(|e| <rusqlite::Error as Into<Box<dyn std::error::Error>>>::into(e));
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+        let mut idle = self.pool.idle.lock();
+        if idle.len() < DB_READER_POOL_SIZE {
+            idle.push(conn);
+        }
     }
+}
+
+singleton_with_logging!(DbManager, DB_CONN);
+
+fn db_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(data_dir()?.join(DB_NAME))
+}
 
+/// Open the database and migrate it to the latest schema. A database left next to
+/// the executable by older versions is moved to the data dir first, and with the
+/// `encryption` feature a plaintext one is encrypted.
+pub fn init_db() -> Result<Connection, Box<dyn std::error::Error>> {
+    let wal = format!("{}-wal", DB_NAME);
+    let shm = format!("{}-shm", DB_NAME);
+    migrate_legacy_files(&[DB_NAME, &wal, &shm], &data_dir()?);
+    #[cfg(feature = "encryption")]
+    cipher::encrypt_plaintext(&db_path()?, &cipher::database_key()?)?;
+    init_db_at(&db_path()?)
+}
+
+pub fn init_db_at(path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
+    let mut conn = open_db_at(path)?;
+    migrate(&mut conn)?;
     Ok(conn)
 }
 
-/// Insert a new record into the specified table.
-///
-/// ## Example
-///
-/// ```ignore
-/// use super::*;
-///
-/// let params = params![
-///     "2023-01-01 12:00:00",
-///     "Visual Studio Code"
-/// ];
-///
-/// let result = insert(TABLE::APP_USAGE_LOGS, &params);
-/// ```
-pub fn insert(
-    conn: &Connection,
-    table_name: &str,
-    params: &[&dyn rusqlite::ToSql],
-) -> Result<(), rusqlite::Error> {
-    let query = match table_name {
-        TABLE::APP_USAGE_LOGS => "INSERT INTO app_usage_logs (time, app_name) VALUES (?, ?)",
-        TABLE::DAILY_APP_USAGE => {
-            "INSERT INTO daily_app_usage (date, app_name, total_usage) VALUES (?, ?, ?)"
-        }
-        TABLE::DAILY_USAGE_STATS => {
-            "INSERT INTO daily_usage_stats (date, total_usage) VALUES (?, ?)"
-        }
-        _ => {
-            return Err(rusqlite::Error::InvalidParameterName(format!(
-                "Invalid table name: {}",
-                table_name
-            )));
-        }
-    };
+/// Open a connection to the database, which `init_db` already migrated.
+pub fn open_db() -> Result<Connection, Box<dyn std::error::Error>> {
+    open_db_at(&db_path()?)
+}
 
-    conn.execute(query, params)?;
-    Ok(())
+/// Open a connection in WAL mode, so readers and the writer don't block each other.
+pub fn open_db_at(path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
+    let conn = open_connection(path, OpenFlags::default())?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    conn.busy_timeout(Duration::from_millis(DB_BUSY_TIMEOUT_MS))?;
+    Ok(conn)
+}
+
+/// Open a connection to a database of the app, the live one or a backup. With the
+/// `encryption` feature it's keyed with the database key.
+pub fn open_connection(
+    path: &Path,
+    flags: OpenFlags,
+) -> Result<Connection, Box<dyn std::error::Error>> {
+    let conn = Connection::open_with_flags(path, flags)?;
+    #[cfg(feature = "encryption")]
+    cipher::apply_key(&conn, &cipher::database_key()?)?;
+    Ok(conn)
 }
@@ -1,19 +1,18 @@
-use super::file::get_exe_path;
+use super::paths::data_dir;
+use crate::constants::config::LOG_DIR_NAME;
 use chrono::Local;
 use simplelog::*;
-use std::fs::File;
+use std::fs::{self, File};
 use std::{env, fmt};
 use time::macros::format_description;
 
 pub fn init_logger() -> Result<(), Box<dyn std::error::Error>> {
-    let exe_path = get_exe_path()?;
-    let log_path = exe_path
-        .parent()
-        .ok_or("Failed to get exe parent dir")?
-        .join(format!(
-            "pc-monitor_{}.log",
-            Local::now().format("%Y-%m-%d_%H-%M-%S")
-        ));
+    let log_dir = data_dir()?.join(LOG_DIR_NAME);
+    fs::create_dir_all(&log_dir)?;
+    let log_path = log_dir.join(format!(
+        "pc-monitor_{}.log",
+        Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
     let log_file = File::create(&log_path)?;
 
     let config = ConfigBuilder::new()
@@ -60,6 +59,7 @@ pub enum Type {
     Autostart,
     Window,
     Report,
+    Database,
 }
 
 impl fmt::Display for Type {
@@ -72,6 +72,7 @@ impl fmt::Display for Type {
             Type::Autostart => write!(f, "[Autostart]"),
             Type::Window => write!(f, "[Window]"),
             Type::Report => write!(f, "[Report]"),
+            Type::Database => write!(f, "[Database]"),
         }
     }
 }
@@ -0,0 +1,690 @@
+use crate::constants::db::TABLE;
+use crate::logging;
+use crate::utils::logging::Type;
+use crate::utils::time::local_offset_secs;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{Connection, Transaction, params};
+use std::fmt;
+
+/// A step of the database schema, applied once when `PRAGMA user_version` is below
+/// its version.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: fn(&Transaction) -> Result<(), rusqlite::Error>,
+}
+
+/// All migrations in the order they are applied. Versions start at 1 and never change
+/// once released, new steps are appended.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_base_tables",
+        up: create_base_tables,
+    },
+    Migration {
+        version: 2,
+        name: "add_window_titles",
+        up: add_window_titles,
+    },
+    Migration {
+        version: 3,
+        name: "create_app_sessions",
+        up: create_app_sessions,
+    },
+    Migration {
+        version: 4,
+        name: "epoch_timestamps",
+        up: epoch_timestamps,
+    },
+    Migration {
+        version: 5,
+        name: "backfill_app_sessions",
+        up: backfill_app_sessions,
+    },
+    Migration {
+        version: 6,
+        name: "create_finalized_days",
+        up: create_finalized_days,
+    },
+    Migration {
+        version: 7,
+        name: "index_session_ends",
+        up: index_session_ends,
+    },
+    Migration {
+        version: 8,
+        name: "create_hourly_app_usage",
+        up: create_hourly_app_usage,
+    },
+    Migration {
+        version: 9,
+        name: "create_daily_focus_stats",
+        up: create_daily_focus_stats,
+    },
+    Migration {
+        version: 10,
+        name: "create_categories",
+        up: create_categories,
+    },
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The database was written by a newer version of the app
+    TooNew {
+        version: u32,
+        latest: u32,
+    },
+    /// A migration step failed and was rolled back
+    Step {
+        version: u32,
+        name: &'static str,
+        source: rusqlite::Error,
+    },
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::TooNew { version, latest } => write!(
+                f,
+                "Database schema version {} is newer than the latest known version {}",
+                version, latest
+            ),
+            MigrationError::Step {
+                version,
+                name,
+                source,
+            } => write!(f, "Migration {} ({}) failed: {}", version, name, source),
+            MigrationError::Sqlite(e) => write!(f, "Failed to read schema version: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MigrationError::TooNew { .. } => None,
+            MigrationError::Step { source, .. } => Some(source),
+            MigrationError::Sqlite(e) => Some(e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+/// Bring the database schema up to the latest version. Returns the version reached.
+pub fn migrate(conn: &mut Connection) -> Result<u32, MigrationError> {
+    migrate_with(conn, MIGRATIONS)
+}
+
+/// Apply the pending `migrations`, each in its own transaction together with the
+/// bump of `user_version`.
+pub fn migrate_with(
+    conn: &mut Connection,
+    migrations: &[Migration],
+) -> Result<u32, MigrationError> {
+    let current = user_version(conn)?;
+    let mut version = current;
+    let latest = migrations.last().map_or(0, |m| m.version);
+    if current > latest {
+        return Err(MigrationError::TooNew {
+            version: current,
+            latest,
+        });
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        logging!(
+            info,
+            Type::Database,
+            false,
+            "Applying migration {} ({})",
+            migration.version,
+            migration.name
+        );
+        let step = |e| MigrationError::Step {
+            version: migration.version,
+            name: migration.name,
+            source: e,
+        };
+        let tx = conn.transaction().map_err(step)?;
+        (migration.up)(&tx).map_err(step)?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(step)?;
+        tx.commit().map_err(step)?;
+        version = migration.version;
+    }
+    Ok(version)
+}
+
+/// The version the latest migration brings the schema to
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+pub fn user_version(conn: &Connection) -> Result<u32, rusqlite::Error> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if row.get::<_, String>(1)? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The tables of the first release. Databases of that time have no `user_version`, so
+/// the tables may already exist.
+fn create_base_tables(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY,
+            time DATETIME NOT NULL,
+            app_name TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY,
+            date DATETIME NOT NULL,
+            app_name TEXT NOT NULL,
+            total_usage INTEGER NOT NULL,
+            UNIQUE (date, app_name)
+        );
+        CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY,
+            date DATETIME NOT NULL,
+            total_usage INTEGER NOT NULL,
+            UNIQUE (date)
+        );",
+        TABLE::APP_USAGE_LOGS,
+        TABLE::DAILY_APP_USAGE,
+        TABLE::DAILY_USAGE_STATS
+    ))
+}
+
+fn add_window_titles(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    // Builds before versioned migrations added the column by themselves
+    if has_column(tx, TABLE::APP_USAGE_LOGS, "title")? {
+        return Ok(());
+    }
+    tx.execute_batch(&format!(
+        "ALTER TABLE {} ADD COLUMN title TEXT NOT NULL DEFAULT ''",
+        TABLE::APP_USAGE_LOGS
+    ))
+}
+
+fn create_app_sessions(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {table} (
+            id INTEGER PRIMARY KEY,
+            start DATETIME NOT NULL,
+            end DATETIME NOT NULL,
+            app_name TEXT NOT NULL,
+            title TEXT NOT NULL DEFAULT '',
+            source TEXT NOT NULL DEFAULT ''
+        );
+        CREATE INDEX IF NOT EXISTS idx_app_sessions_start ON {table} (start);",
+        table = TABLE::APP_SESSIONS
+    ))
+}
+
+/// Replace the UTC time strings by epoch milliseconds plus the UTC offset, in seconds,
+/// of the local time zone at that time.
+fn epoch_timestamps(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE TABLE {logs}_new (
+            id INTEGER PRIMARY KEY,
+            time_ms INTEGER NOT NULL,
+            utc_offset INTEGER NOT NULL,
+            app_name TEXT NOT NULL,
+            title TEXT NOT NULL DEFAULT ''
+        );
+        CREATE TABLE {sessions}_new (
+            id INTEGER PRIMARY KEY,
+            start_ms INTEGER NOT NULL,
+            end_ms INTEGER NOT NULL,
+            utc_offset INTEGER NOT NULL,
+            app_name TEXT NOT NULL,
+            title TEXT NOT NULL DEFAULT '',
+            source TEXT NOT NULL DEFAULT ''
+        );",
+        logs = TABLE::APP_USAGE_LOGS,
+        sessions = TABLE::APP_SESSIONS
+    ))?;
+
+    {
+        let mut select = tx.prepare(&format!(
+            "SELECT id, time, app_name, title FROM {}",
+            TABLE::APP_USAGE_LOGS
+        ))?;
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {}_new (id, time_ms, utc_offset, app_name, title) VALUES (?, ?, ?, ?, ?)",
+            TABLE::APP_USAGE_LOGS
+        ))?;
+        let mut rows = select.query([])?;
+        let mut dropped = 0;
+        while let Some(row) = rows.next()? {
+            let Some(time) = parse_utc(&row.get::<_, String>(1)?) else {
+                dropped += 1;
+                continue;
+            };
+            insert.execute(params![
+                row.get::<_, i64>(0)?,
+                time.timestamp_millis(),
+                local_offset_secs(time),
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?
+            ])?;
+        }
+
+        let mut select = tx.prepare(&format!(
+            "SELECT id, start, end, app_name, title, source FROM {}",
+            TABLE::APP_SESSIONS
+        ))?;
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {}_new (id, start_ms, end_ms, utc_offset, app_name, title, source) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            TABLE::APP_SESSIONS
+        ))?;
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let (Some(start), Some(end)) = (
+                parse_utc(&row.get::<_, String>(1)?),
+                parse_utc(&row.get::<_, String>(2)?),
+            ) else {
+                dropped += 1;
+                continue;
+            };
+            insert.execute(params![
+                row.get::<_, i64>(0)?,
+                start.timestamp_millis(),
+                end.timestamp_millis(),
+                local_offset_secs(start),
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?
+            ])?;
+        }
+        if dropped > 0 {
+            logging!(
+                warn,
+                Type::Database,
+                true,
+                "Dropped {} logs and sessions with an unreadable time",
+                dropped
+            );
+        }
+    }
+
+    tx.execute_batch(&format!(
+        "DROP TABLE {logs};
+        ALTER TABLE {logs}_new RENAME TO {logs};
+        CREATE INDEX idx_app_usage_logs_time ON {logs} (time_ms);
+        DROP TABLE {sessions};
+        ALTER TABLE {sessions}_new RENAME TO {sessions};
+        CREATE INDEX idx_app_sessions_start ON {sessions} (start_ms);",
+        logs = TABLE::APP_USAGE_LOGS,
+        sessions = TABLE::APP_SESSIONS
+    ))
+}
+
+/// The time strings of the old schema, in UTC
+fn parse_utc(time: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Sessions of the time before the tracker maintained them are rebuilt from the logs.
+///
+/// A copy of `segments_from_logs` as it was at this version, so that later changes to
+/// the statistics don't change what old databases upgrade to.
+fn backfill_app_sessions(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    const SUSPEND_GAP_MS: i64 = 180 * 1000;
+    const HEARTBEAT: &str = "Heartbeat";
+    const SOURCE: &str = "logs";
+    const IGNORED: &[&str] = &[
+        "LockApp.exe",
+        "Windows 资源管理器",
+        "Windows Explorer",
+        "Windows Terminal Host",
+        "ShellHost",
+        "任务管理器",
+        "TaskManager",
+        "Application Exited",
+        "User Idle",
+    ];
+    /// `(time_ms, utc_offset, app_name, title)`
+    type Log = (i64, i32, String, String);
+
+    let sessions: i64 = tx.query_row(
+        &format!("SELECT COUNT(*) FROM {}", TABLE::APP_SESSIONS),
+        [],
+        |row| row.get(0),
+    )?;
+    if sessions > 0 {
+        return Ok(());
+    }
+    let mut select = tx.prepare(&format!(
+        "SELECT time_ms, utc_offset, app_name, title FROM {} ORDER BY time_ms, id",
+        TABLE::APP_USAGE_LOGS
+    ))?;
+    let mut insert = tx.prepare(&format!(
+        "INSERT INTO {} (start_ms, end_ms, utc_offset, app_name, title, source)
+        VALUES (?, ?, ?, ?, ?, ?)",
+        TABLE::APP_SESSIONS
+    ))?;
+    let mut inserted = 0;
+    // Credit the window of `log` from its time up to `end`
+    let mut push = |log: &Log, end: i64| -> Result<(), rusqlite::Error> {
+        let (start, utc_offset, app_name, title) = log;
+        if IGNORED.contains(&app_name.as_str()) || end <= *start {
+            return Ok(());
+        }
+        insert.execute(params![start, end, utc_offset, app_name, title, SOURCE])?;
+        inserted += 1;
+        Ok(())
+    };
+
+    let mut current: Option<Log> = None;
+    let mut last_seen: Option<i64> = None;
+    let mut has_heartbeat = false;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let log: Log = (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?);
+        let time = log.0;
+        let is_heartbeat = log.2 == HEARTBEAT;
+        has_heartbeat |= is_heartbeat;
+
+        if let Some(last) = last_seen
+            && has_heartbeat
+            && time - last > SUSPEND_GAP_MS
+            && let Some(pre) = current.take()
+        {
+            push(&pre, last)?;
+            current = Some((time, log.1, pre.2, pre.3));
+        }
+        if !is_heartbeat {
+            if let Some(pre) = current.take() {
+                push(&pre, time)?;
+            }
+            current = Some(log);
+        }
+        last_seen = Some(time);
+    }
+    if let (Some(pre), Some(last)) = (current, last_seen) {
+        push(&pre, last)?;
+    }
+    logging!(
+        info,
+        Type::Database,
+        false,
+        "Rebuilt {} app sessions from app usage logs",
+        inserted
+    );
+    Ok(())
+}
+
+/// Days whose aggregates were computed once they couldn't change anymore, the others
+/// are recomputed by `finalize_past_days`.
+fn create_finalized_days(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE TABLE {} (
+            date TEXT PRIMARY KEY,
+            finalized_ms INTEGER NOT NULL
+        );",
+        TABLE::FINALIZED_DAYS
+    ))
+}
+
+/// Sessions are looked up by the days they overlap, which bounds their end too.
+fn index_session_ends(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE INDEX idx_app_sessions_end ON {} (end_ms);",
+        TABLE::APP_SESSIONS
+    ))
+}
+
+/// The hours are filled in when days are recomputed, unfinalize them all so the
+/// history is too.
+fn create_hourly_app_usage(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE TABLE {hourly} (
+            date TEXT NOT NULL,
+            hour INTEGER NOT NULL,
+            app_name TEXT NOT NULL,
+            total_usage INTEGER NOT NULL,
+            PRIMARY KEY (date, hour, app_name)
+        );
+        DELETE FROM {finalized};",
+        hourly = TABLE::HOURLY_APP_USAGE,
+        finalized = TABLE::FINALIZED_DAYS
+    ))
+}
+
+/// Like the hourly usage, the focus stats of the history are filled in by recomputing it
+fn create_daily_focus_stats(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE TABLE {focus} (
+            date TEXT PRIMARY KEY,
+            app_switches INTEGER NOT NULL,
+            longest_session INTEGER NOT NULL,
+            median_session INTEGER NOT NULL,
+            short_sessions INTEGER NOT NULL
+        );
+        DELETE FROM {finalized};",
+        focus = TABLE::DAILY_FOCUS_STATS,
+        finalized = TABLE::FINALIZED_DAYS
+    ))
+}
+
+/// Each app is in at most one category
+fn create_categories(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(&format!(
+        "CREATE TABLE {categories} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE {app_categories} (
+            app_name TEXT PRIMARY KEY,
+            category_id INTEGER NOT NULL REFERENCES {categories} (id)
+        );
+        CREATE INDEX idx_app_categories_category ON {app_categories} (category_id);",
+        categories = TABLE::CATEGORIES,
+        app_categories = TABLE::APP_CATEGORIES
+    ))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    /// A database of the first release, with logs but no version
+    fn v0_fixture() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app_usage_logs (
+                id INTEGER PRIMARY KEY,
+                time DATETIME NOT NULL,
+                app_name TEXT NOT NULL
+            );
+            CREATE TABLE daily_app_usage (
+                id INTEGER PRIMARY KEY,
+                date DATETIME NOT NULL,
+                app_name TEXT NOT NULL,
+                total_usage INTEGER NOT NULL,
+                UNIQUE (date, app_name)
+            );
+            CREATE TABLE daily_usage_stats (
+                id INTEGER PRIMARY KEY,
+                date DATETIME NOT NULL,
+                total_usage INTEGER NOT NULL,
+                UNIQUE (date)
+            );
+            INSERT INTO app_usage_logs (time, app_name) VALUES
+                ('2025-09-01 09:00:00', 'Visual Studio Code'),
+                ('2025-09-01 09:30:00', 'Firefox'),
+                ('2025-09-01 10:00:00', 'Application Exited'),
+                ('not a time', 'Firefox');
+            INSERT INTO daily_app_usage (date, app_name, total_usage) VALUES
+                ('2025-09-01', 'Visual Studio Code', 1800);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    pub fn test_migrate_empty_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert!(has_column(&conn, TABLE::APP_USAGE_LOGS, "title").unwrap());
+        assert_eq!(count(&conn, TABLE::APP_SESSIONS), 0);
+
+        // Running again is a no-op
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    pub fn test_migrate_v0_fixture() {
+        let mut conn = v0_fixture();
+        assert_eq!(user_version(&conn).unwrap(), 0);
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+
+        assert_eq!(count(&conn, TABLE::APP_USAGE_LOGS), 3);
+        assert_eq!(count(&conn, TABLE::DAILY_APP_USAGE), 1);
+        let title: String = conn
+            .query_row("SELECT title FROM app_usage_logs LIMIT 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(title, "");
+
+        let ms = |time| parse_utc(time).unwrap().timestamp_millis();
+        let (time_ms, utc_offset): (i64, i32) = conn
+            .query_row(
+                "SELECT time_ms, utc_offset FROM app_usage_logs WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(time_ms, ms("2025-09-01 09:00:00"));
+        assert_eq!(
+            utc_offset,
+            local_offset_secs(parse_utc("2025-09-01 09:00:00").unwrap())
+        );
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT start_ms, end_ms, app_name, source FROM app_sessions ORDER BY start_ms",
+            )
+            .unwrap();
+        let sessions: Vec<(i64, i64, String, String)> = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            sessions,
+            vec![
+                (
+                    ms("2025-09-01 09:00:00"),
+                    ms("2025-09-01 09:30:00"),
+                    "Visual Studio Code".into(),
+                    "logs".into()
+                ),
+                (
+                    ms("2025-09-01 09:30:00"),
+                    ms("2025-09-01 10:00:00"),
+                    "Firefox".into(),
+                    "logs".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_migrate_unversioned_titles() {
+        // Builds before versioned migrations added titles and sessions without a version
+        let mut conn = v0_fixture();
+        conn.execute_batch(
+            "ALTER TABLE app_usage_logs ADD COLUMN title TEXT NOT NULL DEFAULT '';
+            CREATE TABLE app_sessions (
+                id INTEGER PRIMARY KEY,
+                start DATETIME NOT NULL,
+                end DATETIME NOT NULL,
+                app_name TEXT NOT NULL,
+                title TEXT NOT NULL DEFAULT '',
+                source TEXT NOT NULL DEFAULT ''
+            );
+            INSERT INTO app_sessions (start, end, app_name, title, source) VALUES
+                ('2025-09-01 09:00:00', '2025-09-01 10:00:00', 'Visual Studio Code', '', 'input_hook');",
+        )
+        .unwrap();
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+        // Existing sessions are kept, not rebuilt
+        assert_eq!(count(&conn, TABLE::APP_SESSIONS), 1);
+    }
+
+    #[test]
+    pub fn test_failed_step_rolls_back() {
+        let migrations = [
+            Migration {
+                version: 1,
+                name: "create_base_tables",
+                up: create_base_tables,
+            },
+            Migration {
+                version: 2,
+                name: "broken",
+                up: |tx| {
+                    tx.execute_batch(
+                        "CREATE TABLE half_done (id INTEGER); INSERT INTO missing VALUES (1);",
+                    )
+                },
+            },
+        ];
+        let mut conn = Connection::open_in_memory().unwrap();
+        let err = migrate_with(&mut conn, &migrations).unwrap_err();
+        assert!(matches!(
+            err,
+            MigrationError::Step {
+                version: 2,
+                name: "broken",
+                ..
+            }
+        ));
+        assert!(err.to_string().starts_with("Migration 2 (broken) failed"));
+        assert_eq!(user_version(&conn).unwrap(), 1);
+        assert!(conn.prepare("SELECT * FROM half_done").is_err());
+    }
+
+    #[test]
+    pub fn test_newer_database_rejected() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+        assert!(matches!(
+            migrate(&mut conn),
+            Err(MigrationError::TooNew { .. })
+        ));
+    }
+}
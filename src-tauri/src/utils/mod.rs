@@ -1,7 +1,18 @@
 pub mod autostart;
+pub mod backup;
+#[cfg(feature = "encryption")]
+pub mod cipher;
+pub mod config;
 pub mod db;
 pub mod file;
 pub mod logging;
+pub mod migration;
+pub mod paths;
+pub mod repo;
 pub mod singleton;
 pub mod test;
+pub mod time;
+#[cfg(target_os = "linux")]
+pub mod wayland;
 pub mod window;
+pub mod writer;
@@ -0,0 +1,165 @@
+use super::config::ConfigManager;
+use super::file::get_exe_path;
+use crate::constants::config::{APP_IDENTIFIER, DATA_DIR_ENV};
+use crate::logging;
+use crate::utils::logging::Type;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::{env, fs, io};
+
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Get the dir of the config file, `com.yzh.pc-monitor` in the platform config dir
+/// (`$XDG_CONFIG_HOME` on Linux, `%APPDATA%` on Windows).
+pub fn config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(dirs::config_dir()
+        .ok_or("Failed to get config dir")?
+        .join(APP_IDENTIFIER))
+}
+
+/// Get the dir of the database and log files, created if missing.
+///
+/// It's resolved once per launch, so a changed `data_dir` setting applies on the
+/// next one.
+pub fn data_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(dir) = DATA_DIR.get() {
+        return Ok(dir.clone());
+    }
+    let dir = resolve_data_dir(
+        env::var_os(DATA_DIR_ENV),
+        ConfigManager::global().get().data_dir,
+        dirs::data_dir(),
+    )
+    .ok_or("Failed to get data dir")?;
+    fs::create_dir_all(&dir)?;
+    Ok(DATA_DIR.get_or_init(|| dir).clone())
+}
+
+/// The env variable comes first, then the setting, then `com.yzh.pc-monitor` in the
+/// platform data dir (`$XDG_DATA_HOME` on Linux, `%APPDATA%` on Windows).
+fn resolve_data_dir(
+    env_dir: Option<OsString>,
+    config_dir: Option<PathBuf>,
+    platform_dir: Option<PathBuf>,
+) -> Option<PathBuf> {
+    env_dir
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or(config_dir)
+        .or_else(|| platform_dir.map(|dir| dir.join(APP_IDENTIFIER)))
+}
+
+/// Move the files `names` out of the executable's dir, where older versions kept
+/// them, to `dir`. Errors are logged, a failed move leaves the old files in place.
+pub fn migrate_legacy_files(names: &[&str], dir: &Path) {
+    let result = get_exe_path().and_then(|exe_path| {
+        let legacy_dir = exe_path.parent().ok_or("Failed to get exe parent dir")?;
+        Ok(move_files(names, legacy_dir, dir)?)
+    });
+    if let Err(e) = result {
+        logging!(
+            warn,
+            Type::Setup,
+            true,
+            "Failed to move data out of the install dir: {}",
+            e
+        );
+    }
+}
+
+/// Move the files `names` from `from` to `to`, unless `to` already has the first one.
+///
+/// The first file is moved last, so its presence marks a complete move. Files missing
+/// in `from` are skipped.
+fn move_files(names: &[&str], from: &Path, to: &Path) -> io::Result<()> {
+    let Some(first) = names.first() else {
+        return Ok(());
+    };
+    if from == to || to.join(first).exists() || !from.join(first).exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(to)?;
+    for name in names.iter().rev() {
+        let source = from.join(name);
+        if source.exists() {
+            move_file(&source, &to.join(name))?;
+            logging!(
+                info,
+                Type::Setup,
+                true,
+                "Moved {} to {}",
+                source.display(),
+                to.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // Renaming fails across file systems and in read-only dirs, copy instead
+    fs::copy(from, to)?;
+    if let Err(e) = fs::remove_file(from) {
+        logging!(
+            warn,
+            Type::Setup,
+            true,
+            "Failed to remove {}: {}",
+            from.display(),
+            e
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_resolve_data_dir() {
+        let platform = Some(PathBuf::from("/home/user/.local/share"));
+        let config = Some(PathBuf::from("/data/config"));
+        assert_eq!(
+            resolve_data_dir(Some("/data/env".into()), config.clone(), platform.clone()),
+            Some(PathBuf::from("/data/env"))
+        );
+        assert_eq!(
+            resolve_data_dir(Some("".into()), config.clone(), platform.clone()),
+            config
+        );
+        assert_eq!(
+            resolve_data_dir(None, None, platform),
+            Some(PathBuf::from("/home/user/.local/share").join(APP_IDENTIFIER))
+        );
+        assert_eq!(resolve_data_dir(None, None, None), None);
+    }
+
+    #[test]
+    pub fn test_move_files() {
+        let root = env::temp_dir().join(format!("pc_monitor_paths_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let (from, to) = (root.join("install"), root.join("data"));
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join("app.db"), "db").unwrap();
+        fs::write(from.join("app.db-wal"), "wal").unwrap();
+
+        let names = ["app.db", "app.db-wal", "app.db-shm"];
+        move_files(&names, &from, &to).unwrap();
+        assert_eq!(fs::read_to_string(to.join("app.db")).unwrap(), "db");
+        assert_eq!(fs::read_to_string(to.join("app.db-wal")).unwrap(), "wal");
+        assert!(!to.join("app.db-shm").exists());
+        assert!(!from.join("app.db").exists());
+
+        // Once moved, a file left behind doesn't overwrite the new one
+        fs::write(from.join("app.db"), "stale").unwrap();
+        move_files(&names, &from, &to).unwrap();
+        assert_eq!(fs::read_to_string(to.join("app.db")).unwrap(), "db");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,816 @@
+use crate::constants::db::MAX_UTC_OFFSET_MS;
+use crate::utils::time::wall_clock_day_bounds;
+use chrono::NaiveDate;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::Serialize;
+
+/// A row of `app_usage_logs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRow {
+    pub time_ms: i64,
+    /// Seconds east of UTC when the log was written
+    pub utc_offset: i32,
+    pub app_name: String,
+    pub title: String,
+}
+
+/// A row of `app_sessions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRow {
+    pub id: i64,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    /// Seconds east of UTC when the session started
+    pub utc_offset: i32,
+    pub app_name: String,
+    pub title: String,
+    pub source: String,
+}
+
+/// A row of `daily_app_usage`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyAppUsage {
+    pub date: NaiveDate,
+    pub app_name: String,
+    /// Seconds
+    pub total_usage: u64,
+}
+
+/// A row of `hourly_app_usage`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HourlyAppUsage {
+    /// The local day, which may start after midnight
+    pub date: NaiveDate,
+    /// The hour of the wall clock, from 0 to 23
+    pub hour: u32,
+    pub app_name: String,
+    /// Seconds
+    pub total_usage: u64,
+}
+
+/// How fragmented the usage of a day was, a row of `daily_focus_stats` without its date
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FocusStats {
+    /// Times the foreground app changed
+    pub app_switches: u32,
+    /// Seconds of the longest stretch in one app
+    pub longest_session: u64,
+    /// Seconds
+    pub median_session: u64,
+    /// Stretches shorter than `FOCUS_SHORT_SESSION_SECS`
+    pub short_sessions: u32,
+}
+
+/// A row of `categories`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Category {
+    pub id: i64,
+    pub name: String,
+}
+
+/// A row of `daily_usage_stats`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyUsageStats {
+    pub date: NaiveDate,
+    /// Seconds
+    pub total_usage: u64,
+}
+
+/// The usage of an app over a period, ranked among all apps
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppRank {
+    /// From 1, apps with the same usage share a rank
+    pub rank: u32,
+    pub app_name: String,
+    /// Seconds
+    pub total_usage: u64,
+    /// Percentage of the usage of all apps over the period
+    pub share: f64,
+    /// Seconds over the previous period of the same length
+    pub previous_usage: u64,
+    /// `total_usage - previous_usage`, in seconds
+    pub change: i64,
+}
+
+fn parse_date(row: &Row, idx: usize) -> Result<NaiveDate, rusqlite::Error> {
+    let date = row.get::<_, String>(idx)?;
+    NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn session_row(row: &Row) -> Result<SessionRow, rusqlite::Error> {
+    Ok(SessionRow {
+        id: row.get(0)?,
+        start_ms: row.get(1)?,
+        end_ms: row.get(2)?,
+        utc_offset: row.get(3)?,
+        app_name: row.get(4)?,
+        title: row.get(5)?,
+        source: row.get(6)?,
+    })
+}
+
+fn daily_app_usage_row(row: &Row) -> Result<DailyAppUsage, rusqlite::Error> {
+    Ok(DailyAppUsage {
+        date: parse_date(row, 0)?,
+        app_name: row.get(1)?,
+        total_usage: row.get(2)?,
+    })
+}
+
+pub fn insert_log(conn: &Connection, log: &LogRow) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached(
+        "INSERT INTO app_usage_logs (time_ms, utc_offset, app_name, title) VALUES (?1, ?2, ?3, ?4)",
+    )?
+    .execute(params![
+        log.time_ms,
+        log.utc_offset,
+        log.app_name,
+        log.title
+    ])?;
+    Ok(())
+}
+
+pub fn count_logs(conn: &Connection) -> Result<i64, rusqlite::Error> {
+    conn.prepare_cached("SELECT COUNT(*) FROM app_usage_logs")?
+        .query_row([], |row| row.get(0))
+}
+
+/// Get the local dates with logs before { date }, by the wall clock at the time they
+/// were written, in order. Days start at `day_start_hour`.
+pub fn log_days_before(
+    conn: &Connection,
+    date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<Vec<NaiveDate>, rusqlite::Error> {
+    let (start_of_day, _) = wall_clock_day_bounds(date, day_start_hour)?;
+    // The first condition only narrows the scan down to the index
+    conn.prepare_cached(
+        "SELECT DISTINCT date((time_ms + utc_offset * 1000 - ?2) / 1000, 'unixepoch') AS day
+        FROM app_usage_logs
+        WHERE time_ms < ?1 + ?3 AND time_ms + utc_offset * 1000 < ?1
+        ORDER BY day",
+    )?
+    .query_map(
+        params![
+            start_of_day,
+            day_start_hour as i64 * 3600 * 1000,
+            MAX_UTC_OFFSET_MS
+        ],
+        |row| parse_date(row, 0),
+    )?
+    .collect()
+}
+
+/// Delete the logs of { local_date }, by the wall clock at the time they were written,
+/// with days starting at `day_start_hour`
+pub fn delete_logs_during(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<(), rusqlite::Error> {
+    let (start_of_day, end_of_day) = wall_clock_day_bounds(local_date, day_start_hour)?;
+    // The first condition only narrows the scan down to the index
+    conn.prepare_cached(
+        "DELETE FROM app_usage_logs WHERE time_ms BETWEEN ?1 AND ?2
+        AND time_ms + utc_offset * 1000 >= ?3 AND time_ms + utc_offset * 1000 < ?4",
+    )?
+    .execute(params![
+        start_of_day - MAX_UTC_OFFSET_MS,
+        end_of_day + MAX_UTC_OFFSET_MS,
+        start_of_day,
+        end_of_day
+    ])?;
+    Ok(())
+}
+
+/// Insert a session, the `id` of `session` is ignored. Returns the id of the new row.
+pub fn insert_session(conn: &Connection, session: &SessionRow) -> Result<i64, rusqlite::Error> {
+    conn.prepare_cached(
+        "INSERT INTO app_sessions (start_ms, end_ms, utc_offset, app_name, title, source)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(params![
+        session.start_ms,
+        session.end_ms,
+        session.utc_offset,
+        session.app_name,
+        session.title,
+        session.source
+    ])?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Move the end of the latest session to `end_ms`, if it's the one which started at
+/// `start_ms`. Once another database is restored, its latest session is left as it is.
+pub fn update_latest_session_end(
+    conn: &Connection,
+    start_ms: i64,
+    end_ms: i64,
+) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached(
+        "UPDATE app_sessions SET end_ms = ?2
+        WHERE id = (SELECT MAX(id) FROM app_sessions) AND start_ms = ?1",
+    )?
+    .execute(params![start_ms, end_ms])?;
+    Ok(())
+}
+
+/// Move the end of the latest session to `end_ms`, unless it ended more than
+/// `max_gap_ms` before.
+pub fn extend_latest_session(
+    conn: &Connection,
+    end_ms: i64,
+    max_gap_ms: i64,
+) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached(
+        "UPDATE app_sessions SET end_ms = ?1
+        WHERE id = (SELECT MAX(id) FROM app_sessions) AND end_ms < ?1 AND ?1 - end_ms <= ?2",
+    )?
+    .execute(params![end_ms, max_gap_ms])?;
+    Ok(())
+}
+
+/// Get the latest session, the only one the tracker may still extend
+pub fn latest_session(conn: &Connection) -> Result<Option<SessionRow>, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT id, start_ms, end_ms, utc_offset, app_name, title, source FROM app_sessions
+        ORDER BY id DESC LIMIT 1",
+    )?
+    .query_row([], session_row)
+    .optional()
+}
+
+#[cfg(test)]
+pub fn count_sessions(conn: &Connection) -> Result<i64, rusqlite::Error> {
+    conn.prepare_cached("SELECT COUNT(*) FROM app_sessions")?
+        .query_row([], |row| row.get(0))
+}
+
+/// Get the sessions overlapping { local_date }, by the wall clock at the time they
+/// started, with days starting at `day_start_hour`
+pub fn sessions_during(
+    conn: &Connection,
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<Vec<SessionRow>, rusqlite::Error> {
+    let (start_of_day, end_of_day) = wall_clock_day_bounds(local_date, day_start_hour)?;
+    // The first two conditions only narrow the scan down to the indexes
+    conn.prepare_cached(
+        "SELECT id, start_ms, end_ms, utc_offset, app_name, title, source FROM app_sessions
+        WHERE end_ms > ?1 AND start_ms < ?2
+        AND end_ms + utc_offset * 1000 > ?3 AND start_ms + utc_offset * 1000 < ?4
+        ORDER BY start_ms, id",
+    )?
+    .query_map(
+        params![
+            start_of_day - MAX_UTC_OFFSET_MS,
+            end_of_day + MAX_UTC_OFFSET_MS,
+            start_of_day,
+            end_of_day
+        ],
+        session_row,
+    )?
+    .collect()
+}
+
+pub fn upsert_daily_app_usage(
+    conn: &Connection,
+    usage: &DailyAppUsage,
+) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached(
+        "INSERT INTO daily_app_usage (date, app_name, total_usage) VALUES (?1, ?2, ?3)
+        ON CONFLICT(date, app_name) DO UPDATE SET total_usage = ?3",
+    )?
+    .execute(params![
+        usage.date.to_string(),
+        usage.app_name,
+        usage.total_usage
+    ])?;
+    Ok(())
+}
+
+/// Delete the daily app usage of { date }
+pub fn delete_daily_app_usage(conn: &Connection, date: NaiveDate) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached("DELETE FROM daily_app_usage WHERE date = ?1")?
+        .execute(params![date.to_string()])?;
+    Ok(())
+}
+
+/// Get the daily app usage from `start_date` to `end_date`, both included
+pub fn daily_app_usage_between(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<Vec<DailyAppUsage>, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT date, app_name, total_usage FROM daily_app_usage
+        WHERE date BETWEEN ?1 AND ?2 ORDER BY date, app_name",
+    )?
+    .query_map(
+        params![start_date.to_string(), end_date.to_string()],
+        daily_app_usage_row,
+    )?
+    .collect()
+}
+
+/// Get the sum of the daily app usage of { date }
+pub fn daily_app_usage_total(conn: &Connection, date: NaiveDate) -> Result<u64, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT COALESCE(SUM(total_usage), 0) FROM daily_app_usage WHERE date = ?1",
+    )?
+    .query_row(params![date.to_string()], |row| row.get(0))
+}
+
+pub fn upsert_daily_usage_stats(
+    conn: &Connection,
+    stats: &DailyUsageStats,
+) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached(
+        "INSERT INTO daily_usage_stats (date, total_usage) VALUES (?1, ?2)
+        ON CONFLICT(date) DO UPDATE SET total_usage = ?2",
+    )?
+    .execute(params![stats.date.to_string(), stats.total_usage])?;
+    Ok(())
+}
+
+/// Get the daily usage stats from `start_date` to `end_date`, both included
+pub fn daily_totals(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<Vec<DailyUsageStats>, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT date, total_usage FROM daily_usage_stats
+        WHERE date BETWEEN ?1 AND ?2 ORDER BY date",
+    )?
+    .query_map(
+        params![start_date.to_string(), end_date.to_string()],
+        |row| {
+            Ok(DailyUsageStats {
+                date: parse_date(row, 0)?,
+                total_usage: row.get(1)?,
+            })
+        },
+    )?
+    .collect()
+}
+
+pub fn finalize_day(
+    conn: &Connection,
+    date: NaiveDate,
+    finalized_ms: i64,
+) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached(
+        "INSERT INTO finalized_days (date, finalized_ms) VALUES (?1, ?2)
+        ON CONFLICT(date) DO UPDATE SET finalized_ms = ?2",
+    )?
+    .execute(params![date.to_string(), finalized_ms])?;
+    Ok(())
+}
+
+/// Replace the hourly app usage of { date } by `usage`
+pub fn replace_hourly_app_usage(
+    conn: &Connection,
+    date: NaiveDate,
+    usage: &[HourlyAppUsage],
+) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached("DELETE FROM hourly_app_usage WHERE date = ?1")?
+        .execute(params![date.to_string()])?;
+    let mut insert = conn.prepare_cached(
+        "INSERT INTO hourly_app_usage (date, hour, app_name, total_usage) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for usage in usage {
+        insert.execute(params![
+            usage.date.to_string(),
+            usage.hour,
+            usage.app_name,
+            usage.total_usage
+        ])?;
+    }
+    Ok(())
+}
+
+pub fn upsert_daily_focus_stats(
+    conn: &Connection,
+    date: NaiveDate,
+    stats: &FocusStats,
+) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached(
+        "INSERT INTO daily_focus_stats
+            (date, app_switches, longest_session, median_session, short_sessions)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(date) DO UPDATE SET
+            app_switches = ?2, longest_session = ?3, median_session = ?4, short_sessions = ?5",
+    )?
+    .execute(params![
+        date.to_string(),
+        stats.app_switches,
+        stats.longest_session,
+        stats.median_session,
+        stats.short_sessions
+    ])?;
+    Ok(())
+}
+
+/// Get the focus stats from `start_date` to `end_date`, both included
+pub fn daily_focus_stats_between(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<Vec<(NaiveDate, FocusStats)>, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT date, app_switches, longest_session, median_session, short_sessions
+        FROM daily_focus_stats WHERE date BETWEEN ?1 AND ?2 ORDER BY date",
+    )?
+    .query_map(
+        params![start_date.to_string(), end_date.to_string()],
+        |row| {
+            let stats = FocusStats {
+                app_switches: row.get(1)?,
+                longest_session: row.get(2)?,
+                median_session: row.get(3)?,
+                short_sessions: row.get(4)?,
+            };
+            Ok((parse_date(row, 0)?, stats))
+        },
+    )?
+    .collect()
+}
+
+/// Get the usage of each hour from `start_date` to `end_date`, both included, summed
+/// over all apps or only `app_name` if provided. Returns `(date, hour, seconds)`.
+pub fn hourly_totals(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    app_name: Option<&str>,
+) -> Result<Vec<(NaiveDate, u32, u64)>, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT date, hour, SUM(total_usage) FROM hourly_app_usage
+        WHERE date BETWEEN ?1 AND ?2 AND (?3 IS NULL OR app_name = ?3)
+        GROUP BY date, hour ORDER BY date, hour",
+    )?
+    .query_map(
+        params![start_date.to_string(), end_date.to_string(), app_name],
+        |row| Ok((parse_date(row, 0)?, row.get(1)?, row.get(2)?)),
+    )?
+    .collect()
+}
+
+/// Rank the apps by their usage from `start_date` to `end_date`, both included, and
+/// compare it with the one from `previous_start` to `previous_end`. Returns the `limit`
+/// first apps, the shares are of the usage of all apps.
+///
+/// The ignored apps never make it into `daily_app_usage`, there's no need to skip them.
+pub fn app_ranking(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    previous_start: NaiveDate,
+    previous_end: NaiveDate,
+    limit: u32,
+) -> Result<Vec<AppRank>, rusqlite::Error> {
+    conn.prepare_cached(
+        "WITH current AS (
+            SELECT app_name, SUM(total_usage) AS total FROM daily_app_usage
+            WHERE date BETWEEN ?1 AND ?2 GROUP BY app_name
+        ), previous AS (
+            SELECT app_name, SUM(total_usage) AS total FROM daily_app_usage
+            WHERE date BETWEEN ?3 AND ?4 GROUP BY app_name
+        )
+        SELECT
+            RANK() OVER (ORDER BY current.total DESC),
+            app_name,
+            current.total,
+            current.total * 100.0 / SUM(current.total) OVER (),
+            COALESCE(previous.total, 0),
+            current.total - COALESCE(previous.total, 0)
+        FROM current LEFT JOIN previous USING (app_name)
+        WHERE current.total > 0
+        ORDER BY current.total DESC, app_name
+        LIMIT ?5",
+    )?
+    .query_map(
+        params![
+            start_date.to_string(),
+            end_date.to_string(),
+            previous_start.to_string(),
+            previous_end.to_string(),
+            limit
+        ],
+        |row| {
+            Ok(AppRank {
+                rank: row.get(0)?,
+                app_name: row.get(1)?,
+                total_usage: row.get(2)?,
+                share: row.get(3)?,
+                previous_usage: row.get(4)?,
+                change: row.get(5)?,
+            })
+        },
+    )?
+    .collect()
+}
+
+/// Get the total app usage from `start_date` to `end_date`, both included, and how many
+/// apps were used
+pub fn usage_summary(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<(u64, u32), rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT COALESCE(SUM(total_usage), 0), COUNT(DISTINCT app_name) FROM daily_app_usage
+        WHERE date BETWEEN ?1 AND ?2 AND total_usage > 0",
+    )?
+    .query_row(
+        params![start_date.to_string(), end_date.to_string()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+pub fn categories(conn: &Connection) -> Result<Vec<Category>, rusqlite::Error> {
+    conn.prepare_cached("SELECT id, name FROM categories ORDER BY name")?
+        .query_map([], category_row)?
+        .collect()
+}
+
+pub fn category_by_id(conn: &Connection, id: i64) -> Result<Option<Category>, rusqlite::Error> {
+    conn.prepare_cached("SELECT id, name FROM categories WHERE id = ?1")?
+        .query_row(params![id], category_row)
+        .optional()
+}
+
+pub fn category_by_name(
+    conn: &Connection,
+    name: &str,
+) -> Result<Option<Category>, rusqlite::Error> {
+    conn.prepare_cached("SELECT id, name FROM categories WHERE name = ?1")?
+        .query_row(params![name], category_row)
+        .optional()
+}
+
+fn category_row(row: &Row) -> Result<Category, rusqlite::Error> {
+    Ok(Category {
+        id: row.get(0)?,
+        name: row.get(1)?,
+    })
+}
+
+pub fn insert_category(conn: &Connection, name: &str) -> Result<i64, rusqlite::Error> {
+    conn.prepare_cached("INSERT INTO categories (name) VALUES (?1)")?
+        .execute(params![name])?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn rename_category(conn: &Connection, id: i64, name: &str) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached("UPDATE categories SET name = ?2 WHERE id = ?1")?
+        .execute(params![id, name])?;
+    Ok(())
+}
+
+/// Delete a category, its apps become uncategorized
+pub fn delete_category(conn: &Connection, id: i64) -> Result<(), rusqlite::Error> {
+    conn.prepare_cached("DELETE FROM app_categories WHERE category_id = ?1")?
+        .execute(params![id])?;
+    conn.prepare_cached("DELETE FROM categories WHERE id = ?1")?
+        .execute(params![id])?;
+    Ok(())
+}
+
+/// Put `app_name` in the category `category_id`, or in none if it's None
+pub fn set_app_category(
+    conn: &Connection,
+    app_name: &str,
+    category_id: Option<i64>,
+) -> Result<(), rusqlite::Error> {
+    match category_id {
+        Some(category_id) => conn
+            .prepare_cached(
+                "INSERT INTO app_categories (app_name, category_id) VALUES (?1, ?2)
+                ON CONFLICT(app_name) DO UPDATE SET category_id = ?2",
+            )?
+            .execute(params![app_name, category_id])?,
+        None => conn
+            .prepare_cached("DELETE FROM app_categories WHERE app_name = ?1")?
+            .execute(params![app_name])?,
+    };
+    Ok(())
+}
+
+/// Get the category id of each categorized app, as `(app_name, category_id)`
+pub fn app_categories(conn: &Connection) -> Result<Vec<(String, i64)>, rusqlite::Error> {
+    conn.prepare_cached("SELECT app_name, category_id FROM app_categories ORDER BY app_name")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+/// Get the usage of each category by day from `start_date` to `end_date`, both
+/// included. Returns `(date, category, seconds)`, the category is None for the apps
+/// without one.
+pub fn category_usage_between(
+    conn: &Connection,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<Vec<(NaiveDate, Option<String>, u64)>, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT usage.date, categories.name, SUM(usage.total_usage)
+        FROM daily_app_usage AS usage
+        LEFT JOIN app_categories USING (app_name)
+        LEFT JOIN categories ON categories.id = app_categories.category_id
+        WHERE usage.date BETWEEN ?1 AND ?2
+        GROUP BY usage.date, categories.name
+        ORDER BY usage.date, categories.name",
+    )?
+    .query_map(
+        params![start_date.to_string(), end_date.to_string()],
+        |row| Ok((parse_date(row, 0)?, row.get(1)?, row.get(2)?)),
+    )?
+    .collect()
+}
+
+/// Delete the daily aggregates of all days, so they are all recomputed
+pub fn clear_aggregates(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "DELETE FROM daily_app_usage;
+        DELETE FROM hourly_app_usage;
+        DELETE FROM daily_focus_stats;
+        DELETE FROM daily_usage_stats;
+        DELETE FROM finalized_days;",
+    )
+}
+
+pub fn clear_finalized_days(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch("DELETE FROM finalized_days;")
+}
+
+pub fn is_day_finalized(conn: &Connection, date: NaiveDate) -> Result<bool, rusqlite::Error> {
+    conn.prepare_cached("SELECT EXISTS (SELECT 1 FROM finalized_days WHERE date = ?1)")?
+        .query_row(params![date.to_string()], |row| row.get(0))
+}
+
+/// Whether the aggregates of { date } were written: its daily usage stats exist and
+/// add up to its daily app usage. A failed write leaves them missing or apart.
+pub fn has_aggregates(conn: &Connection, date: NaiveDate) -> Result<bool, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT EXISTS (
+            SELECT 1 FROM daily_usage_stats WHERE date = ?1 AND total_usage = (
+                SELECT COALESCE(SUM(total_usage), 0) FROM daily_app_usage WHERE date = ?1
+            )
+        )",
+    )?
+    .query_row(params![date.to_string()], |row| row.get(0))
+}
+
+/// Get the size of the database in bytes, without the free pages once vacuumed
+pub fn database_size(conn: &Connection) -> Result<i64, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+    )?
+    .query_row([], |row| row.get(0))
+}
+
+/// Get the local dates overlapped by sessions or with logs, by the wall clock at the
+/// time they started, which aren't finalized yet, in order. Days start at
+/// `day_start_hour`.
+///
+/// Days with logs but no sessions, e.g. only heartbeats while idle, are included so
+/// they get aggregates and their logs can be compacted.
+pub fn unfinalized_days(
+    conn: &Connection,
+    day_start_hour: u32,
+) -> Result<Vec<NaiveDate>, rusqlite::Error> {
+    // Every day from the first to the last one of each session, the end is excluded
+    conn.prepare_cached(
+        "WITH RECURSIVE days (day, last_day) AS (
+            SELECT date((start_ms + utc_offset * 1000 - ?1) / 1000, 'unixepoch'),
+                date((end_ms + utc_offset * 1000 - ?1 - 1) / 1000, 'unixepoch')
+            FROM app_sessions
+            UNION
+            SELECT date(day, '+1 day'), last_day FROM days WHERE day < last_day
+        )
+        SELECT day FROM (
+            SELECT day FROM days
+            UNION
+            SELECT date((time_ms + utc_offset * 1000 - ?1) / 1000, 'unixepoch')
+            FROM app_usage_logs
+        )
+        WHERE day NOT IN (SELECT date FROM finalized_days) ORDER BY day",
+    )?
+    .query_map(params![day_start_hour as i64 * 3600 * 1000], |row| {
+        parse_date(row, 0)
+    })?
+    .collect()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::utils::test::{logs_between, memory_db};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 9, day).unwrap()
+    }
+
+    #[test]
+    pub fn test_logs_between() {
+        let conn = memory_db();
+        for (time_ms, app_name) in [(3000, "C"), (1000, "A"), (2000, "B")] {
+            let log = LogRow {
+                time_ms,
+                utc_offset: 0,
+                app_name: app_name.into(),
+                title: "'quoted' title".into(),
+            };
+            insert_log(&conn, &log).unwrap();
+        }
+        let logs = logs_between(&conn, 1000, 3000).unwrap();
+        let names: Vec<&str> = logs.iter().map(|l| l.app_name.as_str()).collect();
+        assert_eq!(names, ["A", "B"]);
+        assert_eq!(logs[0].title, "'quoted' title");
+    }
+
+    #[test]
+    pub fn test_sessions() {
+        let conn = memory_db();
+        let mut session = SessionRow {
+            id: 0,
+            // 2025-09-01 23:00 in UTC+2
+            start_ms: 1_756_760_400_000,
+            end_ms: 1_756_760_400_000,
+            utc_offset: 2 * 3600,
+            app_name: "Firefox".into(),
+            title: String::new(),
+            source: "mock".into(),
+        };
+        let id = insert_session(&conn, &session).unwrap();
+        update_latest_session_end(&conn, session.start_ms, session.start_ms + 60_000).unwrap();
+        // Not the latest session
+        update_latest_session_end(&conn, 0, session.start_ms + 90_000).unwrap();
+        // Too far from the end, the session is left as it is
+        extend_latest_session(&conn, session.start_ms + 10 * 60_000, 60_000).unwrap();
+        extend_latest_session(&conn, session.start_ms + 2 * 60_000, 60_000).unwrap();
+        assert_eq!(count_sessions(&conn).unwrap(), 1);
+
+        session.id = id;
+        session.end_ms = session.start_ms + 2 * 60_000;
+        assert_eq!(
+            sessions_during(&conn, date(1), 0).unwrap(),
+            vec![session.clone()]
+        );
+        assert!(sessions_during(&conn, date(2), 0).unwrap().is_empty());
+
+        // Until 01:00 on the next day
+        update_latest_session_end(&conn, session.start_ms, session.start_ms + 2 * 3600 * 1000)
+            .unwrap();
+        session.end_ms = session.start_ms + 2 * 3600 * 1000;
+        assert_eq!(sessions_during(&conn, date(2), 0).unwrap(), vec![session]);
+        assert_eq!(unfinalized_days(&conn, 0).unwrap(), vec![date(1), date(2)]);
+    }
+
+    #[test]
+    pub fn test_daily_usage() {
+        let conn = memory_db();
+        for (day, app_name, total_usage) in [(1, "A", 10), (1, "B", 20), (2, "A", 30), (1, "A", 40)]
+        {
+            let usage = DailyAppUsage {
+                date: date(day),
+                app_name: app_name.into(),
+                total_usage,
+            };
+            upsert_daily_app_usage(&conn, &usage).unwrap();
+        }
+        assert_eq!(daily_app_usage_total(&conn, date(1)).unwrap(), 60);
+        assert_eq!(daily_app_usage_total(&conn, date(3)).unwrap(), 0);
+        assert_eq!(
+            daily_app_usage_between(&conn, date(2), date(3))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        for (day, total_usage) in [(1, 60), (2, 30), (1, 70)] {
+            let stats = DailyUsageStats {
+                date: date(day),
+                total_usage,
+            };
+            upsert_daily_usage_stats(&conn, &stats).unwrap();
+        }
+        let totals = daily_totals(&conn, date(1), date(2)).unwrap();
+        assert_eq!(
+            totals,
+            vec![
+                DailyUsageStats {
+                    date: date(1),
+                    total_usage: 70
+                },
+                DailyUsageStats {
+                    date: date(2),
+                    total_usage: 30
+                },
+            ]
+        );
+    }
+}
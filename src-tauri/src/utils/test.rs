@@ -1,3 +1,7 @@
+#[cfg(test)]
+use crate::utils::repo::LogRow;
+#[cfg(test)]
+use rusqlite::{Connection, params};
 use std::collections::HashMap;
 
 pub fn jsonify<K, V>(mp: &HashMap<K, V>) -> String
@@ -7,3 +11,33 @@ where
 {
     serde_json::to_string_pretty(&mp).unwrap_or_default()
 }
+
+/// An in-memory database with all migrations applied
+#[cfg(test)]
+pub fn memory_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::utils::migration::migrate(&mut conn).unwrap();
+    conn
+}
+
+/// Get the logs from `start_ms` included to `end_ms` excluded, ordered by time
+#[cfg(test)]
+pub fn logs_between(
+    conn: &Connection,
+    start_ms: i64,
+    end_ms: i64,
+) -> Result<Vec<LogRow>, rusqlite::Error> {
+    conn.prepare_cached(
+        "SELECT time_ms, utc_offset, app_name, title FROM app_usage_logs
+        WHERE time_ms >= ?1 AND time_ms < ?2 ORDER BY time_ms, id",
+    )?
+    .query_map(params![start_ms, end_ms], |row| {
+        Ok(LogRow {
+            time_ms: row.get(0)?,
+            utc_offset: row.get(1)?,
+            app_name: row.get(2)?,
+            title: row.get(3)?,
+        })
+    })?
+    .collect()
+}
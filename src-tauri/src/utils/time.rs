@@ -0,0 +1,91 @@
+use chrono::{DateTime, Local, NaiveDate, Offset, TimeDelta, TimeZone, Utc};
+use std::fmt;
+
+/// A day start hour outside 0 to 23
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidDayStartHour(pub u32);
+
+impl fmt::Display for InvalidDayStartHour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid day start hour: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidDayStartHour {}
+
+/// Day bounds are query parameters, the repo reports a bad hour as one that can't be
+/// bound.
+impl From<InvalidDayStartHour> for rusqlite::Error {
+    fn from(e: InvalidDayStartHour) -> Self {
+        rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+    }
+}
+
+pub fn check_day_start_hour(day_start_hour: u32) -> Result<(), InvalidDayStartHour> {
+    if day_start_hour >= 24 {
+        return Err(InvalidDayStartHour(day_start_hour));
+    }
+    Ok(())
+}
+
+/// Seconds east of UTC of the local time zone at `time`, recorded with each event.
+pub fn local_offset_secs(time: DateTime<Utc>) -> i32 {
+    Local
+        .offset_from_utc_datetime(&time.naive_utc())
+        .fix()
+        .local_minus_utc()
+}
+
+/// The local date of an event, by the wall clock at the time it happened. Days start
+/// at `day_start_hour`, so earlier events belong to the day before.
+pub fn wall_clock_date(time_ms: i64, utc_offset: i32, day_start_hour: u32) -> NaiveDate {
+    DateTime::from_timestamp_millis(time_ms + utc_offset as i64 * 1000)
+        .unwrap_or_default()
+        .naive_utc()
+        .checked_sub_signed(TimeDelta::hours(day_start_hour as i64))
+        .unwrap_or_default()
+        .date()
+}
+
+/// The local date now, days starting at `day_start_hour`.
+pub fn local_date(day_start_hour: u32) -> NaiveDate {
+    (Local::now().naive_local() - TimeDelta::hours(day_start_hour as i64)).date()
+}
+
+/// The wall-clock bounds of { local_date } in milliseconds, start included and end
+/// excluded, to compare with `time_ms + utc_offset * 1000` of an event. The day runs
+/// from `day_start_hour` to the same hour the next day.
+pub fn wall_clock_day_bounds(
+    local_date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<(i64, i64), InvalidDayStartHour> {
+    let start = local_date
+        .and_hms_opt(day_start_hour, 0, 0)
+        .ok_or(InvalidDayStartHour(day_start_hour))?
+        .and_utc()
+        .timestamp_millis();
+    Ok((start, start + 24 * 3600 * 1000))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_wall_clock_day_bounds() {
+        let date = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let (start, end) = wall_clock_day_bounds(date, 4).unwrap();
+        assert_eq!(
+            start,
+            date.and_hms_opt(4, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp_millis()
+        );
+        assert_eq!(end - start, 24 * 3600 * 1000);
+        assert_eq!(
+            wall_clock_day_bounds(date, 24),
+            Err(InvalidDayStartHour(24))
+        );
+    }
+}
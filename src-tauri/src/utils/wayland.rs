@@ -0,0 +1,403 @@
+use crate::logging;
+use crate::singleton_with_logging;
+use crate::utils::logging::Type;
+use crate::utils::window::{ForegroundWindow, app_name_from_pid, friendly_name_from_app_id};
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+/// The window focused in a Wayland compositor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FocusedWindow {
+    /// The Wayland `app_id`, or the X11 class of an Xwayland window
+    pub app_id: Option<String>,
+    pub pid: Option<u32>,
+    pub title: Option<String>,
+}
+
+impl FocusedWindow {
+    /// Map the window to an app name, preferring the process over the `app_id`.
+    pub fn app_name(&self) -> String {
+        if let Some(pid) = self.pid.filter(|pid| *pid > 0) {
+            let name = app_name_from_pid(pid);
+            if name != "unknown" {
+                return name;
+            }
+        }
+        match &self.app_id {
+            Some(app_id) => friendly_name_from_app_id(app_id).unwrap_or_else(|| app_id.clone()),
+            None => "unknown".into(),
+        }
+    }
+
+    pub fn foreground(&self) -> ForegroundWindow {
+        ForegroundWindow {
+            app_name: self.app_name(),
+            title: self.title.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// A wlroots compositor which exposes the focused window through IPC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Compositor {
+    /// The sway IPC socket (`$SWAYSOCK`)
+    Sway(PathBuf),
+    /// The Hyprland request socket and event socket
+    Hyprland { request: PathBuf, event: PathBuf },
+}
+
+/// The sway IPC message types used here.
+struct SwayMsg;
+
+impl SwayMsg {
+    const SUBSCRIBE: u32 = 2;
+    const GET_TREE: u32 = 4;
+    const EVENT_WINDOW: u32 = 0x8000_0003;
+}
+
+const SWAY_MAGIC: &[u8] = b"i3-ipc";
+
+impl Compositor {
+    /// Detect the running compositor from the environment.
+    pub fn detect() -> Option<Self> {
+        if let Some(sock) = env::var_os("SWAYSOCK") {
+            return Some(Compositor::Sway(PathBuf::from(sock)));
+        }
+        let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+        // Hyprland >= 0.40 keeps its sockets in $XDG_RUNTIME_DIR, older versions in /tmp
+        let dir = env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr").join(&signature))
+            .filter(|dir| dir.exists())
+            .unwrap_or_else(|| PathBuf::from("/tmp/hypr").join(&signature));
+        Some(Compositor::Hyprland {
+            request: dir.join(".socket.sock"),
+            event: dir.join(".socket2.sock"),
+        })
+    }
+
+    /// Query the window which is focused currently.
+    pub fn focused(&self) -> io::Result<Option<FocusedWindow>> {
+        match self {
+            Compositor::Sway(sock) => {
+                let mut stream = UnixStream::connect(sock)?;
+                sway_send(&mut stream, SwayMsg::GET_TREE, b"")?;
+                let (_, tree) = sway_recv(&mut stream)?;
+                Ok(sway_find_focused(&tree).map(sway_window))
+            }
+            Compositor::Hyprland { request, .. } => {
+                let mut stream = UnixStream::connect(request)?;
+                stream.write_all(b"j/activewindow")?;
+                let mut reply = String::new();
+                stream.read_to_string(&mut reply)?;
+                let window: Value = serde_json::from_str(&reply)?;
+                Ok(hyprland_window(&window))
+            }
+        }
+    }
+
+    /// Subscribe to focus events, calling `on_focus` for every newly focused window
+    /// and whenever the title of the focused window changes.
+    ///
+    /// Blocks until the connection to the compositor is closed.
+    pub fn subscribe<F>(&self, mut on_focus: F) -> io::Result<()>
+    where
+        F: FnMut(FocusedWindow),
+    {
+        match self {
+            Compositor::Sway(sock) => {
+                let mut stream = UnixStream::connect(sock)?;
+                sway_send(&mut stream, SwayMsg::SUBSCRIBE, br#"["window"]"#)?;
+                let (_, reply) = sway_recv(&mut stream)?;
+                if reply["success"] != Value::Bool(true) {
+                    return Err(io::Error::other(format!(
+                        "sway subscribe failed: {}",
+                        reply
+                    )));
+                }
+                loop {
+                    let (msg_type, event) = sway_recv(&mut stream)?;
+                    if msg_type != SwayMsg::EVENT_WINDOW {
+                        continue;
+                    }
+                    let container = &event["container"];
+                    if event["change"] == "focus"
+                        || (event["change"] == "title" && container["focused"] == Value::Bool(true))
+                    {
+                        on_focus(sway_window(container));
+                    }
+                }
+            }
+            Compositor::Hyprland { event, .. } => {
+                let reader = BufReader::new(UnixStream::connect(event)?);
+                for line in reader.lines() {
+                    let line = line?;
+                    let Some(data) = line.strip_prefix("activewindow>>") else {
+                        continue;
+                    };
+                    // The event only carries `class,title`, ask for the pid as well
+                    let (class, title) = data.split_once(',').unwrap_or((data, ""));
+                    let window = self.focused().ok().flatten().unwrap_or(FocusedWindow {
+                        app_id: Some(class.to_string()).filter(|c| !c.is_empty()),
+                        pid: None,
+                        title: Some(title.to_string()).filter(|t| !t.is_empty()),
+                    });
+                    on_focus(window);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn sway_send(stream: &mut UnixStream, msg_type: u32, payload: &[u8]) -> io::Result<()> {
+    let mut msg = Vec::with_capacity(SWAY_MAGIC.len() + 8 + payload.len());
+    msg.extend_from_slice(SWAY_MAGIC);
+    msg.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    msg.extend_from_slice(&msg_type.to_ne_bytes());
+    msg.extend_from_slice(payload);
+    stream.write_all(&msg)
+}
+
+fn sway_recv(stream: &mut UnixStream) -> io::Result<(u32, Value)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != SWAY_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid sway IPC magic",
+        ));
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let msg_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((msg_type, serde_json::from_slice(&payload)?))
+}
+
+/// Walk the sway tree and find the focused node.
+fn sway_find_focused(node: &Value) -> Option<&Value> {
+    if node["focused"] == Value::Bool(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(sway_find_focused)
+}
+
+fn sway_window(node: &Value) -> FocusedWindow {
+    // Xwayland windows have no app_id but carry their X11 class
+    let app_id = node["app_id"]
+        .as_str()
+        .or_else(|| node["window_properties"]["class"].as_str())
+        .map(String::from);
+    FocusedWindow {
+        app_id,
+        pid: node["pid"].as_u64().map(|pid| pid as u32),
+        title: node["name"].as_str().map(String::from),
+    }
+}
+
+fn hyprland_window(window: &Value) -> Option<FocusedWindow> {
+    // `j/activewindow` replies with `{}` when nothing is focused
+    let app_id = window["class"].as_str().filter(|c| !c.is_empty());
+    let pid = window["pid"].as_i64().filter(|pid| *pid > 0);
+    if app_id.is_none() && pid.is_none() {
+        return None;
+    }
+    Some(FocusedWindow {
+        app_id: app_id.map(String::from),
+        pid: pid.map(|pid| pid as u32),
+        title: window["title"].as_str().map(String::from),
+    })
+}
+
+/// Foreground window provider for wlroots compositors.
+///
+/// The [`WaylandSource`](crate::core::activity::WaylandSource) subscribes to the
+/// compositor's focus events and caches the focused window here, so `current_window`
+/// doesn't hit the socket. Without the subscription the compositor is queried.
+pub struct WaylandFocus {
+    compositor: Option<Compositor>,
+    focused: Mutex<Option<ForegroundWindow>>,
+}
+
+impl WaylandFocus {
+    pub fn new() -> Self {
+        let compositor = Compositor::detect();
+        if let Some(compositor) = &compositor {
+            logging!(
+                info,
+                Type::Window,
+                false,
+                "Using compositor IPC for foreground window: {:?}",
+                compositor
+            );
+        }
+        Self {
+            compositor,
+            focused: Mutex::new(None),
+        }
+    }
+
+    /// Cache the window reported by the focus subscription, `None` once it ended.
+    pub fn set_focused(&self, window: Option<ForegroundWindow>) {
+        *self.focused.lock() = window;
+    }
+
+    /// Get the focused window, or `None` when not running under sway or Hyprland.
+    pub fn current_window(&self) -> Option<ForegroundWindow> {
+        let compositor = self.compositor.as_ref()?;
+        if let Some(window) = self.focused.lock().clone() {
+            return Some(window);
+        }
+        match compositor.focused() {
+            Ok(window) => Some(window.map_or_else(ForegroundWindow::unknown, |w| w.foreground())),
+            Err(e) => {
+                logging!(warn, Type::Window, false, "Compositor query failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+singleton_with_logging!(WaylandFocus, WAYLAND_FOCUS);
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+    use std::thread;
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("pc-monitor-{}-{}.sock", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// Serve one connection of a fake sway IPC socket with the given replies.
+    fn fake_sway(path: &Path, replies: Vec<(u32, Value)>) -> thread::JoinHandle<(u32, Vec<u8>)> {
+        let listener = UnixListener::bind(path).unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0u8; 14];
+            stream.read_exact(&mut header).unwrap();
+            let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+            let msg_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+            let mut payload = vec![0u8; len as usize];
+            stream.read_exact(&mut payload).unwrap();
+            for (reply_type, reply) in replies {
+                sway_send(&mut stream, reply_type, reply.to_string().as_bytes()).unwrap();
+            }
+            (msg_type, payload)
+        })
+    }
+
+    #[test]
+    pub fn test_sway_focused() {
+        let path = socket_path("sway-tree");
+        let tree = serde_json::json!({
+            "focused": false,
+            "nodes": [{
+                "focused": false,
+                "nodes": [
+                    { "focused": false, "app_id": "foot", "pid": 1, "name": "~", "nodes": [] },
+                ],
+                "floating_nodes": [
+                    { "focused": true, "app_id": null, "pid": 0, "name": "Untitled",
+                      "window_properties": { "class": "Gimp" }, "nodes": [] },
+                ],
+            }],
+        });
+        let server = fake_sway(&path, vec![(SwayMsg::GET_TREE, tree)]);
+
+        let window = Compositor::Sway(path.clone()).focused().unwrap().unwrap();
+        assert_eq!(window.app_id.as_deref(), Some("Gimp"));
+        assert_eq!(window.title.as_deref(), Some("Untitled"));
+        assert_eq!(server.join().unwrap().0, SwayMsg::GET_TREE);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn test_sway_subscribe() {
+        let path = socket_path("sway-subscribe");
+        let event = |change: &str, app_id: &str, focused: bool| {
+            serde_json::json!({
+                "change": change,
+                "container": { "app_id": app_id, "pid": 0, "name": change, "focused": focused },
+            })
+        };
+        let server = fake_sway(
+            &path,
+            vec![
+                (SwayMsg::SUBSCRIBE, serde_json::json!({ "success": true })),
+                (SwayMsg::EVENT_WINDOW, event("focus", "firefox", true)),
+                (SwayMsg::EVENT_WINDOW, event("title", "foot", false)),
+                (SwayMsg::EVENT_WINDOW, event("title", "firefox", true)),
+                (SwayMsg::EVENT_WINDOW, event("focus", "no.such.app", true)),
+            ],
+        );
+
+        let mut windows = Vec::new();
+        let result = Compositor::Sway(path.clone()).subscribe(|w| windows.push(w.foreground()));
+        assert!(
+            result.is_err(),
+            "subscribe returns once the socket is closed"
+        );
+        let titles: Vec<&str> = windows.iter().map(|w| w.title.as_str()).collect();
+        assert_eq!(titles, vec!["focus", "title", "focus"]);
+        assert_eq!(windows[2].app_name, "no.such.app");
+        let (msg_type, payload) = server.join().unwrap();
+        assert_eq!(msg_type, SwayMsg::SUBSCRIBE);
+        assert_eq!(payload, br#"["window"]"#);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn test_hyprland_subscribe() {
+        let request = socket_path("hypr-request");
+        let event = socket_path("hypr-event");
+        let request_listener = UnixListener::bind(&request).unwrap();
+        let event_listener = UnixListener::bind(&event).unwrap();
+        let request_server = thread::spawn(move || {
+            let (mut stream, _) = request_listener.accept().unwrap();
+            let mut cmd = [0u8; 14];
+            stream.read_exact(&mut cmd).unwrap();
+            assert_eq!(&cmd, b"j/activewindow");
+            let reply = r#"{"class": "kitty", "title": "vim", "pid": -1}"#;
+            stream.write_all(reply.as_bytes()).unwrap();
+        });
+        let event_server = thread::spawn(move || {
+            let (mut stream, _) = event_listener.accept().unwrap();
+            stream
+                .write_all(b"workspace>>2\nactivewindow>>kitty,vim\n")
+                .unwrap();
+        });
+
+        let compositor = Compositor::Hyprland {
+            request: request.clone(),
+            event: event.clone(),
+        };
+        let mut windows = Vec::new();
+        compositor.subscribe(|w| windows.push(w)).unwrap();
+        assert_eq!(
+            windows,
+            vec![FocusedWindow {
+                app_id: Some("kitty".into()),
+                pid: None,
+                title: Some("vim".into()),
+            }]
+        );
+        assert_eq!(windows[0].app_name(), "kitty");
+        request_server.join().unwrap();
+        event_server.join().unwrap();
+        let _ = std::fs::remove_file(&request);
+        let _ = std::fs::remove_file(&event);
+    }
+}
@@ -5,35 +5,101 @@ use std::path::Path;
 use sysinfo::{Pid, System};
 use tauri::Manager;
 
+/// The window in the foreground.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForegroundWindow {
+    /// The friendly name of the app owning the window
+    pub app_name: String,
+    /// The window title, empty if unknown
+    pub title: String,
+}
+
+impl ForegroundWindow {
+    pub fn unknown() -> Self {
+        Self {
+            app_name: "unknown".into(),
+            title: String::new(),
+        }
+    }
+}
+
 #[cfg(target_os = "windows")]
 /// Get the window which is focused currently.
 ///
 /// ## Returns
 ///
-/// The name of the focused window.
-pub fn current_window() -> String {
+/// The app name and title of the focused window.
+pub fn current_window() -> ForegroundWindow {
     use windows::{
         Win32::UI::WindowsAndMessaging::GetForegroundWindow,
+        Win32::UI::WindowsAndMessaging::GetWindowTextLengthW,
+        Win32::UI::WindowsAndMessaging::GetWindowTextW,
         Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId,
     };
     unsafe {
         let hwnd = GetForegroundWindow();
         let mut pid = 0;
         GetWindowThreadProcessId(hwnd, Some(&mut pid));
-        let mut sys = System::new();
-        sys.refresh_processes();
-        if let Some(proc) = sys.process(Pid::from_u32(pid)) {
-            let exe_opt = proc.exe();
-            exe_opt
-                .map(|exe| friendly_name_from_exe(&exe.to_path_buf()))
-                .flatten()
-                .unwrap_or_else(|| proc.name().to_string())
-        } else {
-            "unknown".into()
+        let mut title = vec![0u16; GetWindowTextLengthW(hwnd) as usize + 1];
+        let len = GetWindowTextW(hwnd, &mut title);
+        ForegroundWindow {
+            app_name: app_name_from_pid(pid),
+            title: String::from_utf16_lossy(&title[..len.max(0) as usize]),
         }
     }
 }
 
+#[cfg(target_os = "linux")]
+/// Get the window which is focused currently.
+///
+/// Under sway or Hyprland the focused window is reported by the compositor IPC.
+/// Otherwise the active window is read from `_NET_ACTIVE_WINDOW` on the X11 root
+/// window, and its `_NET_WM_PID` is resolved to a process.
+///
+/// ## Returns
+///
+/// The app name and title of the focused window.
+pub fn current_window() -> ForegroundWindow {
+    if let Some(window) = crate::utils::wayland::WaylandFocus::global().current_window() {
+        return window;
+    }
+    match x11::active_window() {
+        Some(window) => ForegroundWindow {
+            app_name: window
+                .pid
+                .map_or_else(|| "unknown".into(), app_name_from_pid),
+            title: window.title,
+        },
+        None => ForegroundWindow::unknown(),
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+/// Get the window which is focused currently.
+///
+/// Foreground window detection is not supported on this platform yet.
+pub fn current_window() -> ForegroundWindow {
+    ForegroundWindow::unknown()
+}
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+/// Resolve a process id to the friendly name of its application.
+///
+/// Falls back to the process name when the executable carries no friendly name.
+pub fn app_name_from_pid(pid: u32) -> String {
+    let pid = Pid::from_u32(pid);
+    // Only the focused process is needed, refreshing all of them is costly when polling
+    let mut sys = System::new();
+    sys.refresh_process(pid);
+    if let Some(proc) = sys.process(pid) {
+        proc.exe()
+            .and_then(friendly_name_from_exe)
+            .unwrap_or_else(|| proc.name().to_string())
+    } else {
+        "unknown".into()
+    }
+}
+
 #[cfg(target_os = "windows")]
 /// Get the friendly name (ProductName) from exe.
 ///
@@ -136,6 +202,303 @@ fn friendly_name_from_exe(exe: &Path) -> Option<String> {
     }
 }
 
+#[cfg(target_os = "linux")]
+/// Get the friendly name (desktop entry `Name`) from exe.
+///
+/// The XDG application directories are searched for a `.desktop` file whose
+/// `Exec` or `TryExec` points at the executable.
+///
+/// ## Params
+///
+/// - `exe`: The path of a executable file
+///
+/// ## Returns
+///
+/// The friendly name from the matched desktop entry (Option(String)).
+///
+/// ## Example
+///
+/// ```ignore
+/// use super::*;
+///
+/// let path = Path::new("/usr/share/code/code");
+/// let friendly_name = friendly_name_from_exe(path);
+/// assert_eq!(friendly_name.unwrap_or_default(), "Visual Studio Code");
+/// ```
+fn friendly_name_from_exe(exe: &Path) -> Option<String> {
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::LazyLock;
+
+    // Scanning the application directories on every focus change is wasteful
+    static CACHE: LazyLock<Mutex<HashMap<PathBuf, Option<String>>>> =
+        LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    if let Some(name) = CACHE.lock().get(exe) {
+        return name.clone();
+    }
+    let name = desktop::application_dirs()
+        .iter()
+        .find_map(|dir| desktop::find_name_in_dir(dir, exe));
+    CACHE.lock().insert(exe.to_path_buf(), name.clone());
+    name
+}
+
+#[cfg(target_os = "linux")]
+/// Get the friendly name (desktop entry `Name`) from a Wayland `app_id`.
+///
+/// Wayland clients set their `app_id` to the basename of their desktop file,
+/// e.g. `org.gnome.Nautilus` for `org.gnome.Nautilus.desktop`.
+pub fn friendly_name_from_app_id(app_id: &str) -> Option<String> {
+    let file_name = format!("{}.desktop", app_id);
+    desktop::application_dirs().iter().find_map(|dir| {
+        let content = std::fs::read_to_string(dir.join(&file_name)).ok()?;
+        desktop::parse(&content).name
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod desktop {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct DesktopEntry {
+        pub name: Option<String>,
+        pub exec: Option<String>,
+        pub try_exec: Option<String>,
+    }
+
+    /// The `applications` directories under `$XDG_DATA_HOME` and `$XDG_DATA_DIRS`.
+    pub fn application_dirs() -> Vec<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+        let data_dirs = env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+        data_home
+            .into_iter()
+            .chain(env::split_paths(&data_dirs))
+            .map(|dir| dir.join("applications"))
+            .collect()
+    }
+
+    /// Find the name of the first desktop entry in `dir` which launches `exe`.
+    pub fn find_name_in_dir(dir: &Path, exe: &Path) -> Option<String> {
+        let entries = fs::read_dir(dir).ok()?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if let Some(name) = find_name_in_dir(&path, exe) {
+                    return Some(name);
+                }
+                continue;
+            }
+            if path.extension().is_none_or(|ext| ext != "desktop") {
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let desktop_entry = parse(&content);
+            if desktop_entry.launches(exe)
+                && let Some(name) = desktop_entry.name
+            {
+                return Some(name);
+            }
+        }
+        None
+    }
+
+    /// Parse the `[Desktop Entry]` group of a desktop file.
+    pub fn parse(content: &str) -> DesktopEntry {
+        let mut desktop_entry = DesktopEntry::default();
+        let mut in_main_group = false;
+        for line in content.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_main_group = line == "[Desktop Entry]";
+                continue;
+            }
+            if !in_main_group {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "Name" => desktop_entry.name = value,
+                "Exec" => desktop_entry.exec = value,
+                "TryExec" => desktop_entry.try_exec = value,
+                _ => {}
+            }
+        }
+        desktop_entry
+    }
+
+    impl DesktopEntry {
+        /// Whether the program of `Exec` or `TryExec` is the executable.
+        pub fn launches(&self, exe: &Path) -> bool {
+            let Some(exe_name) = exe.file_name() else {
+                return false;
+            };
+            [&self.try_exec, &self.exec]
+                .into_iter()
+                .flatten()
+                .filter_map(|cmd| program(cmd))
+                .any(|program| {
+                    let program = Path::new(program);
+                    if program.is_absolute() {
+                        program == exe || fs::canonicalize(program).is_ok_and(|p| p == exe)
+                    } else {
+                        program.as_os_str() == exe_name
+                    }
+                })
+        }
+    }
+
+    /// The program of an `Exec` command line, skipping an `env VAR=value` prefix.
+    fn program(cmd: &str) -> Option<&str> {
+        cmd.split_whitespace()
+            .map(|arg| arg.trim_matches('"'))
+            .find(|arg| *arg != "env" && !arg.contains('='))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use crate::logging;
+    use crate::utils::logging::Type;
+    use parking_lot::Mutex;
+    use std::sync::LazyLock;
+    use x11rb::connection::Connection;
+    use x11rb::errors::{ConnectionError, ReplyError};
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, GetPropertyReply, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    struct X11Conn {
+        conn: RustConnection,
+        root: Window,
+        net_active_window: u32,
+        net_wm_pid: u32,
+        net_wm_name: u32,
+        utf8_string: u32,
+    }
+
+    pub struct ActiveWindow {
+        pub pid: Option<u32>,
+        pub title: String,
+    }
+
+    impl X11Conn {
+        fn connect() -> Option<Self> {
+            let (conn, screen_num) = x11rb::connect(None).ok()?;
+            let root = conn.setup().roots.get(screen_num)?.root;
+            let net_active_window = intern_atom(&conn, b"_NET_ACTIVE_WINDOW")?;
+            let net_wm_pid = intern_atom(&conn, b"_NET_WM_PID")?;
+            let net_wm_name = intern_atom(&conn, b"_NET_WM_NAME")?;
+            let utf8_string = intern_atom(&conn, b"UTF8_STRING")?;
+            Some(Self {
+                conn,
+                root,
+                net_active_window,
+                net_wm_pid,
+                net_wm_name,
+                utf8_string,
+            })
+        }
+
+        /// Errors only if the connection is broken, a missing window or property is `None`.
+        fn get_property(
+            &self,
+            window: Window,
+            property: u32,
+            ty: u32,
+            long_length: u32,
+        ) -> Result<Option<GetPropertyReply>, ConnectionError> {
+            let cookie = self
+                .conn
+                .get_property(false, window, property, ty, 0, long_length)?;
+            match cookie.reply() {
+                Ok(reply) => Ok(Some(reply)),
+                // e.g. the window was closed meanwhile
+                Err(ReplyError::X11Error(_)) => Ok(None),
+                Err(ReplyError::ConnectionError(e)) => Err(e),
+            }
+        }
+
+        fn property(
+            &self,
+            window: Window,
+            property: u32,
+            ty: AtomEnum,
+        ) -> Result<Option<u32>, ConnectionError> {
+            Ok(self
+                .get_property(window, property, ty.into(), 1)?
+                .and_then(|reply| reply.value32()?.next()))
+        }
+
+        /// `_NET_WM_NAME` is UTF-8, the legacy `WM_NAME` is only read as a fallback.
+        fn title(&self, window: Window) -> Result<String, ConnectionError> {
+            for (property, ty) in [
+                (self.net_wm_name, self.utf8_string),
+                (AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()),
+            ] {
+                if let Some(reply) = self.get_property(window, property, ty, 1024)?
+                    && !reply.value.is_empty()
+                {
+                    return Ok(String::from_utf8_lossy(&reply.value).into_owned());
+                }
+            }
+            Ok(String::new())
+        }
+
+        fn active_window(&self) -> Result<Option<ActiveWindow>, ConnectionError> {
+            let Some(window) =
+                self.property(self.root, self.net_active_window, AtomEnum::WINDOW)?
+            else {
+                return Ok(None);
+            };
+            if window == x11rb::NONE {
+                return Ok(None);
+            }
+            Ok(Some(ActiveWindow {
+                pid: self.property(window, self.net_wm_pid, AtomEnum::CARDINAL)?,
+                title: self.title(window)?,
+            }))
+        }
+    }
+
+    fn intern_atom(conn: &RustConnection, name: &[u8]) -> Option<u32> {
+        Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom)
+    }
+
+    /// The connection shared by all lookups. Opened on the first lookup after it's
+    /// `None`, e.g. the app started before the X server or the connection was lost.
+    static CONN: LazyLock<Mutex<Option<X11Conn>>> = LazyLock::new(|| Mutex::new(None));
+
+    /// Get the pid and title of the window referenced by `_NET_ACTIVE_WINDOW`.
+    pub fn active_window() -> Option<ActiveWindow> {
+        let mut conn = CONN.lock();
+        if conn.is_none() {
+            *conn = X11Conn::connect();
+        }
+        match conn.as_ref()?.active_window() {
+            Ok(window) => window,
+            Err(e) => {
+                logging!(warn, Type::Window, false, "X11 connection lost: {}", e);
+                *conn = None;
+                None
+            }
+        }
+    }
+}
+
 #[tauri::command]
 pub fn window_minimize() -> bool {
     WindowManager::minimize()
@@ -287,3 +650,85 @@ impl WindowManager {
         }
     }
 }
+
+#[cfg(all(test, target_os = "linux"))]
+pub mod test {
+    use super::*;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        AtomEnum, ConnectionExt, CreateWindowAux, PropMode, WindowClass,
+    };
+    use x11rb::wrapper::ConnectionExt as _;
+
+    #[test]
+    pub fn test_parse_desktop_entry() {
+        let entry = desktop::parse(
+            "[Desktop Entry]\nName=Visual Studio Code\nExec=env FOO=1 /usr/share/code/code --unity-launch %F\n\n[Desktop Action new-empty-window]\nName=New Empty Window\nExec=/usr/share/code/code --new-window %F\n",
+        );
+        assert_eq!(entry.name.as_deref(), Some("Visual Studio Code"));
+        assert!(entry.launches(Path::new("/usr/share/code/code")));
+        assert!(!entry.launches(Path::new("/usr/bin/firefox")));
+
+        let entry = desktop::parse("[Desktop Entry]\nName=Firefox\nExec=firefox %u\n");
+        assert!(entry.launches(Path::new("/usr/lib/firefox/firefox")));
+    }
+
+    #[test]
+    #[ignore = "requires an X server, run with `xvfb-run cargo test -- --ignored`"]
+    pub fn test_current_window_x11() {
+        let (conn, screen_num) = x11rb::connect(None).expect("Error connecting to X server");
+        let screen = &conn.setup().roots[screen_num];
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            0,
+            0,
+            100,
+            100,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new(),
+        )
+        .unwrap();
+        let intern = |name: &[u8]| conn.intern_atom(false, name).unwrap().reply().unwrap().atom;
+        let net_wm_pid = intern(b"_NET_WM_PID");
+        let net_wm_name = intern(b"_NET_WM_NAME");
+        let utf8_string = intern(b"UTF8_STRING");
+        let net_active_window = intern(b"_NET_ACTIVE_WINDOW");
+
+        // Xvfb runs without a window manager, so act as one
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            net_wm_pid,
+            AtomEnum::CARDINAL,
+            &[std::process::id()],
+        )
+        .unwrap();
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            net_wm_name,
+            utf8_string,
+            "pc-monitor — 测试".as_bytes(),
+        )
+        .unwrap();
+        conn.change_property32(
+            PropMode::REPLACE,
+            screen.root,
+            net_active_window,
+            AtomEnum::WINDOW,
+            &[window],
+        )
+        .unwrap();
+        conn.sync().unwrap();
+
+        let window = current_window();
+        assert_ne!(window.app_name, "unknown");
+        assert_eq!(window.app_name, app_name_from_pid(std::process::id()));
+        assert_eq!(window.title, "pc-monitor — 测试");
+    }
+}
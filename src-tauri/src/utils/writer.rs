@@ -0,0 +1,357 @@
+use crate::constants::db::{WRITER_BATCH_SIZE, WRITER_FLUSH_INTERVAL_MS};
+use crate::logging;
+use crate::singleton_with_logging;
+use crate::utils::db::open_db;
+use crate::utils::logging::Type;
+use crate::utils::repo::{
+    self, DailyAppUsage, DailyUsageStats, FocusStats, HourlyAppUsage, LogRow, SessionRow,
+};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A write to the database, applied by the [`DbWriter`].
+#[derive(Debug)]
+pub enum WriteOp {
+    InsertLog(LogRow),
+    /// Open a session, the `id` is assigned by the database
+    InsertSession(SessionRow),
+    /// Move the end of the latest session, if it started at `start_ms`
+    UpdateLatestSessionEnd {
+        start_ms: i64,
+        end_ms: i64,
+    },
+    /// Move the end of the latest session, unless it ended more than `max_gap_ms`
+    /// before
+    ExtendLatestSession {
+        end_ms: i64,
+        max_gap_ms: i64,
+    },
+    UpsertDailyAppUsage(DailyAppUsage),
+    /// Replace all the daily app usage of `date`
+    ReplaceDailyAppUsage {
+        date: NaiveDate,
+        usage: Vec<DailyAppUsage>,
+    },
+    /// Replace all the hourly app usage of `date`
+    ReplaceHourlyAppUsage {
+        date: NaiveDate,
+        usage: Vec<HourlyAppUsage>,
+    },
+    UpsertDailyUsageStats(DailyUsageStats),
+    UpsertDailyFocusStats {
+        date: NaiveDate,
+        stats: FocusStats,
+    },
+    FinalizeDay {
+        date: NaiveDate,
+        finalized_ms: i64,
+    },
+    InsertCategory {
+        name: String,
+    },
+    RenameCategory {
+        id: i64,
+        name: String,
+    },
+    /// Delete a category, its apps become uncategorized
+    DeleteCategory {
+        id: i64,
+    },
+    /// Put an app in a category, or in none
+    SetAppCategory {
+        app_name: String,
+        category_id: Option<i64>,
+    },
+    /// Delete the daily aggregates of all days, and their finalization
+    ClearAggregates,
+    /// Unmark all days as finalized, so their aggregates are recomputed
+    ClearFinalizedDays,
+    DeleteLogsDuring {
+        date: NaiveDate,
+        day_start_hour: u32,
+    },
+    /// Rebuild the database file to give the free pages back, once the batch is
+    /// committed
+    Vacuum,
+    /// Commit what is buffered, then notify the sender
+    Flush(Sender<()>),
+}
+
+/// Somewhere writes can be sent to.
+///
+/// The app writes through the [`DbWriter`] thread, while a plain [`Connection`] applies
+/// each write right away, which keeps tests synchronous. Functions taking both a
+/// `conn` and a `sink` read through the former and write through the latter.
+pub trait WriteSink {
+    fn write(&self, op: WriteOp) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Return once all writes sent before are committed.
+    fn flush(&self) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+impl WriteSink for Connection {
+    fn write(&self, op: WriteOp) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(apply(self, op)?)
+    }
+
+    fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+fn apply(conn: &Connection, op: WriteOp) -> Result<(), rusqlite::Error> {
+    match op {
+        WriteOp::InsertLog(log) => repo::insert_log(conn, &log),
+        WriteOp::InsertSession(session) => repo::insert_session(conn, &session).map(|_| ()),
+        WriteOp::UpdateLatestSessionEnd { start_ms, end_ms } => {
+            repo::update_latest_session_end(conn, start_ms, end_ms)
+        }
+        WriteOp::ExtendLatestSession { end_ms, max_gap_ms } => {
+            repo::extend_latest_session(conn, end_ms, max_gap_ms)
+        }
+        WriteOp::UpsertDailyAppUsage(usage) => repo::upsert_daily_app_usage(conn, &usage),
+        WriteOp::ReplaceDailyAppUsage { date, usage } => {
+            repo::delete_daily_app_usage(conn, date)?;
+            usage
+                .iter()
+                .try_for_each(|usage| repo::upsert_daily_app_usage(conn, usage))
+        }
+        WriteOp::ReplaceHourlyAppUsage { date, usage } => {
+            repo::replace_hourly_app_usage(conn, date, &usage)
+        }
+        WriteOp::UpsertDailyUsageStats(stats) => repo::upsert_daily_usage_stats(conn, &stats),
+        WriteOp::UpsertDailyFocusStats { date, stats } => {
+            repo::upsert_daily_focus_stats(conn, date, &stats)
+        }
+        WriteOp::FinalizeDay { date, finalized_ms } => repo::finalize_day(conn, date, finalized_ms),
+        WriteOp::InsertCategory { name } => repo::insert_category(conn, &name).map(|_| ()),
+        WriteOp::RenameCategory { id, name } => repo::rename_category(conn, id, &name),
+        WriteOp::DeleteCategory { id } => repo::delete_category(conn, id),
+        WriteOp::SetAppCategory {
+            app_name,
+            category_id,
+        } => repo::set_app_category(conn, &app_name, category_id),
+        WriteOp::ClearAggregates => repo::clear_aggregates(conn),
+        WriteOp::ClearFinalizedDays => repo::clear_finalized_days(conn),
+        WriteOp::DeleteLogsDuring {
+            date,
+            day_start_hour,
+        } => repo::delete_logs_during(conn, date, day_start_hour),
+        WriteOp::Vacuum => conn.execute_batch("VACUUM"),
+        WriteOp::Flush(done) => {
+            let _ = done.send(());
+            Ok(())
+        }
+    }
+}
+
+/// The only connection which writes to the database.
+///
+/// Writes are buffered for up to `WRITER_FLUSH_INTERVAL_MS` or `WRITER_BATCH_SIZE`
+/// operations and committed in one transaction, so input hooks and commands never
+/// wait on the disk. Readers open their own connections, which WAL mode lets run
+/// alongside the writer.
+pub struct DbWriter {
+    sender: Sender<WriteOp>,
+}
+
+impl DbWriter {
+    pub fn new() -> Self {
+        Self::spawn(open_db().expect("Failed to open database for writing"))
+    }
+
+    /// Start the writer thread on `conn`.
+    pub fn spawn(conn: Connection) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || write_loop(conn, receiver));
+        Self { sender }
+    }
+}
+
+singleton_with_logging!(DbWriter, DB_WRITER);
+
+impl WriteSink for DbWriter {
+    fn write(&self, op: WriteOp) -> Result<(), Box<dyn std::error::Error>> {
+        self.sender
+            .send(op)
+            .map_err(|_| "Database writer stopped".into())
+    }
+
+    fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let (done, wait) = mpsc::channel();
+        self.write(WriteOp::Flush(done))?;
+        wait.recv()?;
+        Ok(())
+    }
+}
+
+fn write_loop(mut conn: Connection, receiver: Receiver<WriteOp>) {
+    let flush_interval = Duration::from_millis(WRITER_FLUSH_INTERVAL_MS);
+    // Block until there is something to write, then collect what follows shortly
+    while let Ok(first) = receiver.recv() {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + flush_interval;
+        let mut disconnected = false;
+        while batch.len() < WRITER_BATCH_SIZE && !matches!(batch.last(), Some(WriteOp::Flush(_))) {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(op) => batch.push(op),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
+            }
+        }
+        if let Err(e) = write_batch(&mut conn, batch) {
+            logging!(
+                error,
+                Type::Database,
+                true,
+                "Failed to commit database writes: {}",
+                e
+            );
+        }
+        if disconnected {
+            break;
+        }
+    }
+}
+
+/// Apply `batch` in one transaction. A failing operation is logged and rolled back as a
+/// whole, the rest of the batch is still committed.
+fn write_batch(conn: &mut Connection, batch: Vec<WriteOp>) -> Result<(), rusqlite::Error> {
+    let mut tx = conn.transaction()?;
+    let mut flushed = Vec::new();
+    let mut vacuum = false;
+    for op in batch {
+        match op {
+            // Notify after the commit, not when the op comes up
+            WriteOp::Flush(done) => {
+                flushed.push(done);
+                continue;
+            }
+            // Can't run in a transaction
+            WriteOp::Vacuum => {
+                vacuum = true;
+                continue;
+            }
+            _ => {}
+        }
+        let description = format!("{:?}", op);
+        // Dropped without a commit, a savepoint rolls back
+        let savepoint = tx.savepoint()?;
+        match apply(&savepoint, op) {
+            Ok(()) => savepoint.commit()?,
+            Err(e) => {
+                logging!(
+                    error,
+                    Type::Database,
+                    false,
+                    "Failed to write {}: {}",
+                    description,
+                    e
+                );
+            }
+        }
+    }
+    tx.commit()?;
+    if vacuum && let Err(e) = conn.execute_batch("VACUUM") {
+        logging!(
+            error,
+            Type::Database,
+            true,
+            "Failed to vacuum database: {}",
+            e
+        );
+    }
+    for done in flushed {
+        let _ = done.send(());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::utils::db::{init_db_at, open_db_at};
+    use crate::utils::test::{logs_between, memory_db};
+    use std::fs;
+
+    #[test]
+    pub fn test_failing_op_rolled_back() {
+        let mut conn = memory_db();
+        conn.execute_batch(
+            "CREATE TRIGGER fail BEFORE INSERT ON daily_app_usage WHEN NEW.app_name = 'fail'
+            BEGIN SELECT RAISE(ABORT, 'fail'); END;",
+        )
+        .unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 9, day).unwrap();
+        let usage = |day, app_name: &str| DailyAppUsage {
+            date: date(day),
+            app_name: app_name.to_string(),
+            total_usage: 60,
+        };
+        repo::upsert_daily_app_usage(&conn, &usage(1, "Firefox")).unwrap();
+
+        let batch = vec![
+            // Fails after deleting the day, which must be undone
+            WriteOp::ReplaceDailyAppUsage {
+                date: date(1),
+                usage: vec![usage(1, "Terminal"), usage(1, "fail")],
+            },
+            WriteOp::UpsertDailyAppUsage(usage(2, "Firefox")),
+        ];
+        write_batch(&mut conn, batch).unwrap();
+        assert_eq!(
+            repo::daily_app_usage_between(&conn, date(1), date(2)).unwrap(),
+            vec![usage(1, "Firefox"), usage(2, "Firefox")]
+        );
+    }
+
+    #[test]
+    pub fn test_writer_batches_with_concurrent_reader() {
+        let path =
+            std::env::temp_dir().join(format!("pc_monitor_writer_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let reader = init_db_at(&path).unwrap();
+        let writer = DbWriter::spawn(open_db_at(&path).unwrap());
+
+        for time_ms in 0..1000 {
+            let log = LogRow {
+                time_ms,
+                utc_offset: 0,
+                app_name: "Firefox".into(),
+                title: String::new(),
+            };
+            writer.write(WriteOp::InsertLog(log)).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(logs_between(&reader, 0, 1000).unwrap().len(), 1000);
+
+        // A read transaction on the reader doesn't block the writer in WAL mode
+        let journal_mode: String = reader
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        reader.execute_batch("BEGIN").unwrap();
+        assert_eq!(logs_between(&reader, 0, 2000).unwrap().len(), 1000);
+        let log = LogRow {
+            time_ms: 1000,
+            utc_offset: 0,
+            app_name: "Firefox".into(),
+            title: String::new(),
+        };
+        writer.write(WriteOp::InsertLog(log)).unwrap();
+        writer.flush().unwrap();
+        reader.execute_batch("COMMIT").unwrap();
+        assert_eq!(logs_between(&reader, 0, 2000).unwrap().len(), 1001);
+
+        drop(reader);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}